@@ -1,11 +1,12 @@
-use crate::size::Size;
+use crate::collision;
+use crate::settings::World;
 use avian2d::prelude::*;
 use bevy::prelude::*;
 use std::time::SystemTime;
 
 #[derive(Component)]
 pub struct Ball {
-    pub size: Size,
+    pub size: usize,
     pub bounces: usize,
     pub spawn_time: SystemTime,
 }
@@ -18,6 +19,9 @@ pub struct BallBundle {
     body: RigidBody,
     restitution: Restitution,
     collider: Collider,
+    density: ColliderDensity,
+    layers: CollisionLayers,
+    events: CollisionEventsEnabled,
     mesh: Mesh2d,
     material: MeshMaterial2d<ColorMaterial>,
 }
@@ -26,11 +30,13 @@ impl BallBundle {
     pub fn new(
         position: Vec2,
         initial_velocity: Vec2,
-        size: Size,
-        bounciness: f32,
+        size: usize,
+        world: &World,
         meshes: &mut ResMut<Assets<Mesh>>,
         materials: &mut ResMut<Assets<ColorMaterial>>,
     ) -> Self {
+        let ball_size = world.ball_size(size);
+
         BallBundle {
             marker: Ball {
                 size,
@@ -40,10 +46,15 @@ impl BallBundle {
             transform: Transform::from_xyz(position.x, position.y, 0.0),
             velocity: LinearVelocity(initial_velocity),
             body: RigidBody::Dynamic,
-            restitution: Restitution::new(bounciness),
-            collider: Collider::circle(size.to_radius()),
-            mesh: Mesh2d(meshes.add(Circle::new(size.to_radius()))),
-            material: MeshMaterial2d(materials.add(ColorMaterial::from_color(size.to_color()))),
+            restitution: Restitution::new(ball_size.restitution(world.bounciness)),
+            collider: Collider::circle(ball_size.radius),
+            density: ColliderDensity(ball_size.density),
+            layers: collision::ball_layers(true),
+            events: CollisionEventsEnabled,
+            mesh: Mesh2d(meshes.add(Circle::new(ball_size.radius))),
+            material: MeshMaterial2d(
+                materials.add(ColorMaterial::from_color(ball_size.to_color())),
+            ),
         }
     }
 }
@@ -0,0 +1,223 @@
+use crate::midi::NOTES_PER_OCTAVE;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use strum_macros::EnumIter;
+
+#[derive(Clone, Copy, PartialEq, EnumIter, Serialize, Deserialize)]
+pub enum Chord {
+    Single,
+    Major,
+    Minor,
+    Diminished,
+    Augmented,
+    Sus2,
+    Sus4,
+    Power,
+    Major7,
+    Minor7,
+    Dominant7,
+    HalfDiminished7,
+    Custom,
+}
+
+impl Chord {
+    pub fn intervals(&self) -> &'static [i32] {
+        match self {
+            Chord::Single | Chord::Custom => &[0],
+            Chord::Major => &[0, 4, 7],
+            Chord::Minor => &[0, 3, 7],
+            Chord::Diminished => &[0, 3, 6],
+            Chord::Augmented => &[0, 4, 8],
+            Chord::Sus2 => &[0, 2, 7],
+            Chord::Sus4 => &[0, 5, 7],
+            Chord::Power => &[0, 7, 12],
+            Chord::Major7 => &[0, 4, 7, 11],
+            Chord::Minor7 => &[0, 3, 7, 10],
+            Chord::Dominant7 => &[0, 4, 7, 10],
+            Chord::HalfDiminished7 => &[0, 3, 6, 10],
+        }
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Chord::Single => write!(f, "Single Note"),
+            Chord::Major => write!(f, "Major"),
+            Chord::Minor => write!(f, "Minor"),
+            Chord::Diminished => write!(f, "Diminished"),
+            Chord::Augmented => write!(f, "Augmented"),
+            Chord::Sus2 => write!(f, "Sus2"),
+            Chord::Sus4 => write!(f, "Sus4"),
+            Chord::Power => write!(f, "Power"),
+            Chord::Major7 => write!(f, "Major 7th"),
+            Chord::Minor7 => write!(f, "Minor 7th"),
+            Chord::Dominant7 => write!(f, "Dominant 7th"),
+            Chord::HalfDiminished7 => write!(f, "Half Diminished 7th"),
+            Chord::Custom => write!(f, "Custom"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, EnumIter, Serialize, Deserialize)]
+pub enum Voicing {
+    Close,
+    Open,
+    Drop2,
+}
+
+impl fmt::Display for Voicing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Voicing::Close => write!(f, "Close"),
+            Voicing::Open => write!(f, "Open"),
+            Voicing::Drop2 => write!(f, "Drop 2"),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PadChord {
+    pub chord: Chord,
+    pub inversion: usize,
+    pub voicing: Voicing,
+    pub custom_intervals: Vec<i32>,
+}
+
+impl Default for PadChord {
+    fn default() -> Self {
+        PadChord {
+            chord: Chord::Single,
+            inversion: 0,
+            voicing: Voicing::Close,
+            custom_intervals: vec![0, 4, 7],
+        }
+    }
+}
+
+impl PadChord {
+    pub fn intervals(&self) -> Vec<i32> {
+        if self.chord == Chord::Custom {
+            return self.custom_intervals.clone();
+        }
+
+        self.chord.intervals().to_vec()
+    }
+
+    pub fn stack(&self, root: i32) -> Vec<i32> {
+        let octave = NOTES_PER_OCTAVE as i32;
+
+        let mut intervals = self.intervals();
+        intervals.sort();
+
+        for _ in 0..self.inversion.min(intervals.len().saturating_sub(1)) {
+            let lowest = intervals.remove(0);
+            intervals.push(lowest + octave);
+        }
+
+        match self.voicing {
+            Voicing::Close => {}
+            Voicing::Open => {
+                for (index, interval) in intervals.iter_mut().enumerate() {
+                    if index % 2 == 1 {
+                        *interval += octave;
+                    }
+                }
+            }
+            Voicing::Drop2 => {
+                if intervals.len() >= 2 {
+                    let second_highest = intervals.len() - 2;
+                    intervals[second_highest] -= octave;
+                }
+            }
+        }
+
+        intervals.sort();
+        intervals.dedup();
+        intervals.iter().map(|interval| root + interval).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use strum::IntoEnumIterator;
+
+    const ROOT: i32 = 60;
+
+    fn chord(chord: Chord, inversion: usize, voicing: Voicing) -> PadChord {
+        PadChord {
+            chord,
+            inversion,
+            voicing,
+            ..PadChord::default()
+        }
+    }
+
+    #[test]
+    fn single_note_is_the_root() {
+        assert_eq!(PadChord::default().stack(ROOT), [ROOT]);
+    }
+
+    #[test]
+    fn inversions_raise_the_lowest_notes() {
+        let stack = |inversion| chord(Chord::Major, inversion, Voicing::Close).stack(ROOT);
+
+        assert_eq!(stack(0), [60, 64, 67]);
+        assert_eq!(stack(1), [64, 67, 72]);
+        assert_eq!(stack(2), [67, 72, 76]);
+        assert_eq!(stack(5), stack(2));
+    }
+
+    #[test]
+    fn inversions_stay_within_two_octaves() {
+        let octave = NOTES_PER_OCTAVE as i32;
+
+        for kind in Chord::iter() {
+            for inversion in 0..=kind.intervals().len() {
+                let stack = chord(kind, inversion, Voicing::Close).stack(ROOT);
+
+                assert!(!stack.is_empty(), "{} inversion {}", kind, inversion);
+                assert!(
+                    stack
+                        .iter()
+                        .all(|pitch| (ROOT..ROOT + 2 * octave).contains(pitch)),
+                    "{} inversion {}",
+                    kind,
+                    inversion
+                );
+                assert!(stack.windows(2).all(|pair| pair[0] < pair[1]));
+            }
+        }
+    }
+
+    #[test]
+    fn voicings_spread_the_chord() {
+        assert_eq!(
+            chord(Chord::Major7, 0, Voicing::Open).stack(ROOT),
+            [60, 67, 76, 83]
+        );
+        assert_eq!(
+            chord(Chord::Major7, 0, Voicing::Drop2).stack(ROOT),
+            [55, 60, 64, 71]
+        );
+    }
+
+    #[test]
+    fn custom_chord_with_a_single_interval() {
+        for voicing in Voicing::iter() {
+            for inversion in 0..3 {
+                let pad_chord = PadChord {
+                    chord: Chord::Custom,
+                    inversion,
+                    voicing,
+                    custom_intervals: vec![5],
+                };
+
+                assert_eq!(pad_chord.intervals(), [5]);
+                assert_eq!(pad_chord.stack(ROOT), [ROOT + 5]);
+            }
+        }
+    }
+}
@@ -0,0 +1,251 @@
+use crate::ball::Ball;
+use crate::midi;
+use crate::midi::MidiOutputEvent;
+use crate::pad::PlayingNote;
+use crate::settings::Settings;
+use crate::transport::{advance_internal_clock, Transport};
+use crate::velocity::VelocityCurve;
+use avian2d::prelude::{CollisionLayers, CollisionStarted, LinearVelocity, PhysicsLayer};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+use strum_macros::EnumIter;
+
+pub struct BallCollisionPlugin;
+
+impl Plugin for BallCollisionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CollisionNotes>();
+        app.init_resource::<QuantizedCollisions>();
+        app.add_systems(
+            Update,
+            (
+                update_ball_layers,
+                play_ball_collisions,
+                release_quantized_collisions.after(advance_internal_clock),
+                note_off_collisions,
+            ),
+        );
+    }
+}
+
+#[derive(PhysicsLayer, Default)]
+pub enum Layer {
+    #[default]
+    Default,
+    Ball,
+}
+
+#[derive(Clone, Copy, PartialEq, EnumIter, Serialize, Deserialize)]
+pub enum CollisionNote {
+    SizeInterval,
+    Fixed,
+}
+
+impl fmt::Display for CollisionNote {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CollisionNote::SizeInterval => write!(f, "Size Interval"),
+            CollisionNote::Fixed => write!(f, "Fixed Note"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct BallCollisionNotes {
+    pub enabled: bool,
+    pub note: CollisionNote,
+    pub pitch: u8,
+    pub channel: u8,
+    pub velocity: VelocityCurve,
+}
+
+impl Default for BallCollisionNotes {
+    fn default() -> Self {
+        BallCollisionNotes {
+            enabled: false,
+            note: CollisionNote::SizeInterval,
+            pitch: 72,
+            channel: midi::MIN_CHANNEL,
+            velocity: VelocityCurve::default(),
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct CollisionNotes(HashMap<u8, PlayingNote>);
+
+struct CollisionTrigger {
+    pitches: Vec<u8>,
+    velocity: u8,
+    channel: u8,
+    duration: Duration,
+}
+
+#[derive(Resource, Default)]
+struct QuantizedCollisions(Vec<(u64, CollisionTrigger)>);
+
+pub fn ball_layers(collide: bool) -> CollisionLayers {
+    if collide {
+        CollisionLayers::new(Layer::Ball, [Layer::Default, Layer::Ball])
+    } else {
+        CollisionLayers::new(Layer::Ball, [Layer::Default])
+    }
+}
+
+fn update_ball_layers(mut balls: Query<&mut CollisionLayers, With<Ball>>, settings: Res<Settings>) {
+    let layers = ball_layers(settings.world.ball_collisions);
+
+    for mut ball in balls.iter_mut() {
+        if *ball != layers {
+            *ball = layers;
+        }
+    }
+}
+
+fn play_collision(
+    playing: &mut CollisionNotes,
+    trigger: &CollisionTrigger,
+    midi: &mut EventWriter<MidiOutputEvent>,
+) {
+    for pitch in trigger.pitches.iter() {
+        if let Some(note) = playing.0.get(pitch) {
+            midi.write(MidiOutputEvent::NoteOff {
+                pitch: *pitch,
+                channel: note.channel,
+            });
+        }
+
+        midi.write(MidiOutputEvent::NoteOn {
+            pitch: *pitch,
+            velocity: trigger.velocity,
+            channel: trigger.channel,
+            ball: None,
+        });
+
+        playing.0.insert(
+            *pitch,
+            PlayingNote {
+                channel: trigger.channel,
+                timer: Timer::new(trigger.duration, TimerMode::Once),
+            },
+        );
+    }
+}
+
+fn play_ball_collisions(
+    mut collisions: EventReader<CollisionStarted>,
+    mut playing: ResMut<CollisionNotes>,
+    mut quantized: ResMut<QuantizedCollisions>,
+    balls: Query<(&Ball, &LinearVelocity)>,
+    settings: Res<Settings>,
+    transport: Res<Transport>,
+    mut midi: EventWriter<MidiOutputEvent>,
+) {
+    let collision_notes = &settings.midi.collision_notes;
+
+    for CollisionStarted(first, second) in collisions.read() {
+        if !collision_notes.enabled || !transport.is_playing() {
+            continue;
+        }
+
+        if let (Ok((first, first_velocity)), Ok((second, second_velocity))) =
+            (balls.get(*first), balls.get(*second))
+        {
+            let speed = (first_velocity.0 - second_velocity.0).length();
+
+            let pitches = match collision_notes.note {
+                CollisionNote::SizeInterval => {
+                    let root = settings.midi.key.root;
+                    let mut pitches: Vec<u8> = [first.size, second.size]
+                        .iter()
+                        .map(|size| {
+                            settings
+                                .midi
+                                .pitch
+                                .out_of_range
+                                .apply(settings.midi.pitch.root(
+                                    root,
+                                    &settings.world.ball_size(*size),
+                                    None,
+                                ))
+                        })
+                        .collect();
+                    pitches.sort();
+                    pitches.dedup();
+                    pitches
+                }
+                CollisionNote::Fixed => vec![collision_notes.pitch],
+            };
+
+            let velocity = if settings.midi.fixed_note_velocity.enabled {
+                settings.midi.fixed_note_velocity.value
+            } else {
+                collision_notes.velocity.to_velocity(speed)
+            };
+            let trigger = CollisionTrigger {
+                pitches,
+                velocity,
+                channel: collision_notes.channel,
+                duration: if settings.midi.fixed_note_length.enabled {
+                    Duration::from_millis(settings.midi.fixed_note_length.value)
+                } else {
+                    midi::to_note_duration(speed)
+                },
+            };
+
+            match transport.next_boundary(settings.midi.quantize) {
+                Some(boundary) => quantized.0.push((boundary, trigger)),
+                None => play_collision(&mut playing, &trigger, &mut midi),
+            }
+        }
+    }
+}
+
+fn release_quantized_collisions(
+    mut quantized: ResMut<QuantizedCollisions>,
+    mut playing: ResMut<CollisionNotes>,
+    transport: Res<Transport>,
+    mut midi: EventWriter<MidiOutputEvent>,
+) {
+    if !transport.is_playing() {
+        quantized.0.clear();
+        return;
+    }
+
+    let (due, waiting): (Vec<_>, Vec<_>) = quantized
+        .0
+        .drain(..)
+        .partition(|(boundary, _)| transport.is_due(*boundary));
+    quantized.0 = waiting;
+
+    for (_, trigger) in due {
+        play_collision(&mut playing, &trigger, &mut midi);
+    }
+}
+
+fn note_off_collisions(
+    mut playing: ResMut<CollisionNotes>,
+    transport: Res<Transport>,
+    time: Res<Time>,
+    mut midi: EventWriter<MidiOutputEvent>,
+) {
+    for (_, note) in playing.0.iter_mut() {
+        note.timer.tick(time.delta());
+    }
+
+    playing.0.retain(|pitch, note| {
+        if note.timer.just_finished() || !transport.is_playing() {
+            midi.write(MidiOutputEvent::NoteOff {
+                pitch: *pitch,
+                channel: note.channel,
+            });
+            return false;
+        }
+
+        true
+    });
+}
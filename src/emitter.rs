@@ -0,0 +1,305 @@
+use crate::ball::BallBundle;
+use crate::settings::Settings;
+use crate::transport::Transport;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use strum_macros::EnumIter;
+
+pub struct EmitterPlugin;
+
+impl Plugin for EmitterPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                sync_emitters,
+                run_emitters.after(sync_emitters),
+                draw_emitters,
+            ),
+        );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, EnumIter, Serialize, Deserialize)]
+pub enum Division {
+    Whole,
+    Half,
+    Quarter,
+    Eighth,
+    Sixteenth,
+    HalfTriplet,
+    QuarterTriplet,
+    EighthTriplet,
+    SixteenthTriplet,
+}
+
+impl Division {
+    pub fn to_pulses(self) -> u64 {
+        match self {
+            Division::Whole => 96,
+            Division::Half => 48,
+            Division::Quarter => 24,
+            Division::Eighth => 12,
+            Division::Sixteenth => 6,
+            Division::HalfTriplet => 32,
+            Division::QuarterTriplet => 16,
+            Division::EighthTriplet => 8,
+            Division::SixteenthTriplet => 4,
+        }
+    }
+}
+
+impl fmt::Display for Division {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Division::Whole => write!(f, "1/1"),
+            Division::Half => write!(f, "1/2"),
+            Division::Quarter => write!(f, "1/4"),
+            Division::Eighth => write!(f, "1/8"),
+            Division::Sixteenth => write!(f, "1/16"),
+            Division::HalfTriplet => write!(f, "1/2T"),
+            Division::QuarterTriplet => write!(f, "1/4T"),
+            Division::EighthTriplet => write!(f, "1/8T"),
+            Division::SixteenthTriplet => write!(f, "1/16T"),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EuclideanPattern {
+    pub enabled: bool,
+    pub steps: usize,
+    pub pulses: usize,
+    pub rotation: usize,
+}
+
+impl Default for EuclideanPattern {
+    fn default() -> Self {
+        EuclideanPattern {
+            enabled: false,
+            steps: 8,
+            pulses: 3,
+            rotation: 0,
+        }
+    }
+}
+
+impl EuclideanPattern {
+    pub fn is_hit(&self, step: u64) -> bool {
+        if !self.enabled {
+            return true;
+        }
+
+        if self.steps == 0 {
+            return false;
+        }
+
+        let steps = self.steps as u64;
+        let pulses = self.pulses.min(self.steps) as u64;
+        let position = (step + self.rotation as u64) % steps;
+
+        (position * pulses) % steps < pulses
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmitterSettings {
+    pub enabled: bool,
+    pub position: (f32, f32),
+    pub angle: f32,
+    pub speed: f32,
+    pub size: usize,
+    pub division: Division,
+    pub pattern: EuclideanPattern,
+}
+
+impl Default for EmitterSettings {
+    fn default() -> Self {
+        EmitterSettings {
+            enabled: true,
+            position: (0.0, 150.0),
+            angle: -90.0,
+            speed: 0.0,
+            size: 0,
+            division: Division::Quarter,
+            pattern: EuclideanPattern::default(),
+        }
+    }
+}
+
+impl EmitterSettings {
+    pub fn position(&self) -> Vec2 {
+        Vec2::new(self.position.0, self.position.1)
+    }
+
+    pub fn direction(&self) -> Vec2 {
+        Vec2::from_angle(self.angle.to_radians())
+    }
+}
+
+#[derive(Component)]
+struct Emitter {
+    index: usize,
+    last_step: Option<u64>,
+}
+
+fn sync_emitters(
+    mut commands: Commands,
+    mut emitters: Query<(Entity, &Emitter, &mut Transform)>,
+    settings: Res<Settings>,
+) {
+    if emitters.iter().count() != settings.world.emitters.len() {
+        for (entity, _, _) in emitters.iter() {
+            commands.entity(entity).despawn();
+        }
+
+        for (index, emitter) in settings.world.emitters.iter().enumerate() {
+            let position = emitter.position();
+            commands.spawn((
+                Emitter {
+                    index,
+                    last_step: None,
+                },
+                Transform::from_xyz(position.x, position.y, 0.0),
+            ));
+        }
+
+        return;
+    }
+
+    for (_, emitter, mut transform) in emitters.iter_mut() {
+        if let Some(emitter_settings) = settings.world.emitters.get(emitter.index) {
+            let position = emitter_settings.position();
+            transform.translation.x = position.x;
+            transform.translation.y = position.y;
+        }
+    }
+}
+
+fn run_emitters(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut emitters: Query<(&mut Emitter, &Transform)>,
+    transport: Res<Transport>,
+    settings: Res<Settings>,
+) {
+    for (mut emitter, transform) in emitters.iter_mut() {
+        if let Some(emitter_settings) = settings.world.emitters.get(emitter.index) {
+            let step = transport.pulse() / emitter_settings.division.to_pulses();
+            let is_new_step = emitter.last_step != Some(step);
+            emitter.last_step = Some(step);
+
+            if is_new_step
+                && transport.is_playing()
+                && emitter_settings.enabled
+                && emitter_settings.pattern.is_hit(step)
+            {
+                commands.spawn(BallBundle::new(
+                    transform.translation.truncate(),
+                    emitter_settings.direction() * emitter_settings.speed,
+                    emitter_settings.size,
+                    &settings.world,
+                    &mut meshes,
+                    &mut materials,
+                ));
+            }
+        }
+    }
+}
+
+fn draw_emitters(mut gizmos: Gizmos, settings: Res<Settings>) {
+    const RADIUS: f32 = 8.0;
+    const ARROW_LENGTH: f32 = 30.0;
+
+    for emitter in settings.world.emitters.iter() {
+        let color = if emitter.enabled {
+            settings.world.ball_size(emitter.size).to_color()
+        } else {
+            Color::linear_rgb(0.3, 0.3, 0.3)
+        };
+
+        let position = emitter.position();
+        gizmos.circle_2d(position, RADIUS, color);
+        gizmos.arrow_2d(
+            position,
+            position + emitter.direction() * ARROW_LENGTH,
+            color,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(steps: usize, pulses: usize, rotation: usize) -> String {
+        let pattern = EuclideanPattern {
+            enabled: true,
+            steps,
+            pulses,
+            rotation,
+        };
+
+        (0..steps as u64)
+            .map(|step| if pattern.is_hit(step) { 'x' } else { '.' })
+            .collect()
+    }
+
+    #[test]
+    fn known_patterns() {
+        assert_eq!(pattern(8, 3, 0), "x..x..x.");
+        assert_eq!(pattern(8, 5, 0), "x.x.xx.x");
+        assert_eq!(pattern(4, 1, 0), "x...");
+        assert_eq!(pattern(16, 4, 0), "x...x...x...x...");
+    }
+
+    #[test]
+    fn rotation_shifts_the_pattern() {
+        assert_eq!(pattern(8, 3, 1), "..x..x.x");
+        assert_eq!(pattern(8, 3, 8), pattern(8, 3, 0));
+    }
+
+    #[test]
+    fn empty_and_full_patterns() {
+        assert_eq!(pattern(8, 0, 0), "........");
+        assert_eq!(pattern(8, 8, 0), "xxxxxxxx");
+        assert_eq!(pattern(8, 12, 3), "xxxxxxxx");
+
+        let no_steps = EuclideanPattern {
+            enabled: true,
+            steps: 0,
+            pulses: 3,
+            rotation: 0,
+        };
+        assert!(!no_steps.is_hit(0));
+    }
+
+    #[test]
+    fn patterns_repeat_every_cycle() {
+        let pattern = EuclideanPattern {
+            enabled: true,
+            ..default()
+        };
+
+        for step in 0..8 {
+            assert_eq!(pattern.is_hit(step), pattern.is_hit(step + 8));
+        }
+        assert!(!pattern.is_hit(1));
+    }
+
+    #[test]
+    fn disabled_pattern_always_hits() {
+        let pattern = EuclideanPattern {
+            enabled: false,
+            steps: 8,
+            pulses: 0,
+            rotation: 0,
+        };
+
+        assert!((0..8).all(|step| pattern.is_hit(step)));
+    }
+}
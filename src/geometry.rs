@@ -1,73 +1,114 @@
 use avian2d::math::PI;
-use bevy::math::ops::{cos, sin};
+use bevy::math::ops::{atan2, cos, sin};
 use bevy::math::{Quat, Vec2};
 use bevy::prelude::Transform;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use strum_macros::EnumIter;
 
-#[derive(Clone, Copy, PartialEq, EnumIter)]
+pub const MIN_SIDES: usize = 3;
+pub const MAX_SIDES: usize = 32;
+
+#[derive(Clone, Copy, PartialEq, EnumIter, Serialize, Deserialize)]
 pub enum Shape {
+    Triangle,
     Square,
     Pentagon,
     Hexagon,
     Heptagon,
     Octagon,
+    Polygon,
+    Star,
+    Irregular,
+    Freeform,
 }
 
 impl Shape {
-    pub fn to_string(&self) -> String {
+    pub fn get_num_sides(&self) -> Option<usize> {
         match self {
-            Shape::Square => "Square".to_string(),
-            Shape::Pentagon => "Pentagon".to_string(),
-            Shape::Hexagon => "Hexagon".to_string(),
-            Shape::Heptagon => "Heptagon".to_string(),
-            Shape::Octagon => "Octagon".to_string(),
+            Shape::Triangle => Some(3),
+            Shape::Square => Some(4),
+            Shape::Pentagon => Some(5),
+            Shape::Hexagon => Some(6),
+            Shape::Heptagon => Some(7),
+            Shape::Octagon => Some(8),
+            Shape::Polygon | Shape::Star | Shape::Irregular | Shape::Freeform => None,
         }
     }
+}
 
-    pub fn get_num_sides(&self) -> usize {
+impl fmt::Display for Shape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Shape::Square => 4,
-            Shape::Pentagon => 5,
-            Shape::Hexagon => 6,
-            Shape::Heptagon => 7,
-            Shape::Octagon => 8,
+            Shape::Triangle => write!(f, "Triangle"),
+            Shape::Square => write!(f, "Square"),
+            Shape::Pentagon => write!(f, "Pentagon"),
+            Shape::Hexagon => write!(f, "Hexagon"),
+            Shape::Heptagon => write!(f, "Heptagon"),
+            Shape::Octagon => write!(f, "Octagon"),
+            Shape::Polygon => write!(f, "Polygon"),
+            Shape::Star => write!(f, "Star"),
+            Shape::Irregular => write!(f, "Irregular"),
+            Shape::Freeform => write!(f, "Freeform"),
         }
     }
-
-    pub fn get_side_transforms(&self, centre: Vec2, apothem: f32) -> Vec<Transform> {
-        polygon(centre, apothem, self.get_num_sides())
-    }
 }
 
-fn to_transforms(vertices: Vec<Vec2>, centre: Vec2, turn_angle: f32) -> Vec<Transform> {
-    let mut transforms = Vec::new();
+pub fn polygon(apothem: f32, num_sides: usize) -> Vec<Vec2> {
+    if num_sides < MIN_SIDES {
+        return Vec::new();
+    }
 
-    let mut angle = 0.0;
+    let angle_increment = 2.0 * PI / num_sides as f32;
+    let radius = apothem / cos(angle_increment / 2.0);
 
-    for x in 0..vertices.len() {
-        transforms.push(
-            Transform::from_xyz(centre.x + vertices[x].x, centre.y + vertices[x].y, 0.0)
-                .with_rotation(Quat::from_rotation_z(angle)),
-        );
+    (0..num_sides)
+        .map(|i| {
+            let angle = (i as f32 - 0.5) * angle_increment;
+            Vec2::new(radius * sin(angle), radius * cos(angle))
+        })
+        .collect()
+}
 
-        angle -= turn_angle;
+pub fn star(radius: f32, depth: f32, num_points: usize) -> Vec<Vec2> {
+    if num_points < MIN_SIDES {
+        return Vec::new();
     }
 
-    transforms
+    let angle_increment = PI / num_points as f32;
+
+    (0..num_points * 2)
+        .map(|i| {
+            let angle = i as f32 * angle_increment;
+            let length = if i % 2 == 0 { radius } else { radius * depth };
+            Vec2::new(length * sin(angle), length * cos(angle))
+        })
+        .collect()
 }
 
-pub fn polygon(centre: Vec2, apothem: f32, num_sides: usize) -> Vec<Transform> {
-    let mut mid_points = Vec::new();
-    if num_sides < 3 {
-        return Vec::new();
-    }
+pub fn irregular(apothem: f32, radii: &[f32]) -> Vec<Vec2> {
+    polygon(apothem, radii.len())
+        .into_iter()
+        .zip(radii.iter())
+        .map(|(vertex, radius)| vertex * *radius)
+        .collect()
+}
 
-    let angle_increment = 2.0 * PI / num_sides as f32;
+pub fn edges(vertices: &[Vec2]) -> Vec<(Transform, f32)> {
+    let mut edges = Vec::new();
+
+    for x in 0..vertices.len() {
+        let start = vertices[x];
+        let end = vertices[(x + 1) % vertices.len()];
+        let mid_point = (start + end) / 2.0;
+        let direction = end - start;
 
-    for i in 0..num_sides {
-        let angle = i as f32 * angle_increment;
-        mid_points.push(Vec2::new(apothem * sin(angle), apothem * cos(angle)));
+        edges.push((
+            Transform::from_xyz(mid_point.x, mid_point.y, 0.0)
+                .with_rotation(Quat::from_rotation_z(atan2(direction.y, direction.x))),
+            direction.length(),
+        ));
     }
 
-    to_transforms(mid_points, centre, angle_increment)
+    edges
 }
@@ -0,0 +1,200 @@
+use crate::ball::BallBundle;
+use crate::midi;
+use crate::midi::{MidiInputEvent, Note};
+use crate::scale;
+use crate::settings::{ChannelOverride, Settings};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt;
+use strum_macros::EnumIter;
+
+pub struct KeyboardPlugin;
+
+impl Plugin for KeyboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HeldKeys>();
+        app.add_systems(Update, play_from_keyboard);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, EnumIter, Serialize, Deserialize)]
+pub enum KeyboardMode {
+    Off,
+    SpawnBalls,
+    RetunePads,
+}
+
+impl fmt::Display for KeyboardMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeyboardMode::Off => write!(f, "Off"),
+            KeyboardMode::SpawnBalls => write!(f, "Spawn Balls"),
+            KeyboardMode::RetunePads => write!(f, "Retune Pads"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyboardInput {
+    pub mode: KeyboardMode,
+    pub channel: ChannelOverride,
+    pub lowest: u8,
+    pub highest: u8,
+    pub drop_range: (f32, f32),
+    pub drop_height: f32,
+    pub tombola: usize,
+}
+
+impl Default for KeyboardInput {
+    fn default() -> Self {
+        KeyboardInput {
+            mode: KeyboardMode::Off,
+            channel: ChannelOverride::default(),
+            lowest: 36,
+            highest: 96,
+            drop_range: (-250.0, 250.0),
+            drop_height: 300.0,
+            tombola: 0,
+        }
+    }
+}
+
+impl KeyboardInput {
+    fn drop_position(&self, pitch: u8) -> Vec2 {
+        let span = self.highest.saturating_sub(self.lowest).max(1) as f32;
+        let amount = (pitch.saturating_sub(self.lowest) as f32 / span).clamp(0.0, 1.0);
+        let (left, right) = self.drop_range;
+
+        Vec2::new(left + (right - left) * amount, self.drop_height)
+    }
+}
+
+#[derive(Resource, Default)]
+struct HeldKeys(BTreeSet<u8>);
+
+fn to_octave(pitch: u8) -> i32 {
+    let offset = pitch as i32 - Note::C.to_value(0);
+    offset.div_euclid(midi::NOTES_PER_OCTAVE as i32)
+}
+
+fn play_from_keyboard(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut input: EventReader<MidiInputEvent>,
+    mut held: ResMut<HeldKeys>,
+    mut settings: ResMut<Settings>,
+    mut mode: Local<Option<KeyboardMode>>,
+) {
+    if *mode != Some(settings.midi.keyboard.mode) {
+        held.0.clear();
+        *mode = Some(settings.midi.keyboard.mode);
+    }
+
+    for event in input.read() {
+        if event.message.len() < 3 {
+            continue;
+        }
+
+        let keyboard = &settings.midi.keyboard;
+        let status = event.message[0] & 0xF0;
+        let channel = (event.message[0] & 0x0F) + midi::MIN_CHANNEL;
+        let pitch = event.message[1];
+        let velocity = event.message[2];
+
+        if status == midi::NOTE_OFF_MSG || (status == midi::NOTE_ON_MSG && velocity == 0) {
+            held.0.remove(&pitch);
+            continue;
+        }
+
+        if status != midi::NOTE_ON_MSG
+            || keyboard.mode == KeyboardMode::Off
+            || (keyboard.channel.enabled && keyboard.channel.value != channel)
+        {
+            continue;
+        }
+
+        held.0.insert(pitch);
+
+        match keyboard.mode {
+            KeyboardMode::SpawnBalls => {
+                let octave = to_octave(pitch);
+                let size = settings
+                    .world
+                    .palette
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, ball_size)| (ball_size.octave - octave).abs())
+                    .map(|(index, _)| index)
+                    .unwrap_or_default();
+
+                commands.spawn(BallBundle::new(
+                    keyboard.drop_position(pitch),
+                    Vec2::ZERO,
+                    size,
+                    &settings.world,
+                    &mut meshes,
+                    &mut materials,
+                ));
+            }
+            KeyboardMode::RetunePads => {
+                let key = &settings.midi.key;
+                let mask = key.to_mask();
+                let chord: Vec<Note> = held
+                    .0
+                    .iter()
+                    .map(|pitch| scale::snap(key.root, &mask, Note::from_index(*pitch as usize)))
+                    .collect();
+                let index = keyboard.tombola;
+
+                if let Some(tombola) = settings.world.tombolas.get_mut(index) {
+                    for (pad_index, pad) in tombola.pads.iter_mut().enumerate() {
+                        pad.note = chord[pad_index % chord.len()];
+                    }
+                }
+            }
+            KeyboardMode::Off => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn octaves_follow_middle_c_as_c3() {
+        assert_eq!(to_octave(60), 3);
+        assert_eq!(to_octave(59), 2);
+        assert_eq!(to_octave(71), 3);
+        assert_eq!(to_octave(72), 4);
+        assert_eq!(to_octave(0), -2);
+    }
+
+    #[test]
+    fn drop_position_spans_the_key_range() {
+        let keyboard = KeyboardInput::default();
+
+        assert_eq!(keyboard.drop_position(36), Vec2::new(-250.0, 300.0));
+        assert_eq!(keyboard.drop_position(66), Vec2::new(0.0, 300.0));
+        assert_eq!(keyboard.drop_position(96), Vec2::new(250.0, 300.0));
+    }
+
+    #[test]
+    fn drop_position_clamps_outside_the_key_range() {
+        let keyboard = KeyboardInput::default();
+
+        assert_eq!(keyboard.drop_position(0).x, -250.0);
+        assert_eq!(keyboard.drop_position(127).x, 250.0);
+
+        let single = KeyboardInput {
+            lowest: 60,
+            highest: 60,
+            ..default()
+        };
+        assert_eq!(single.drop_position(60).x, -250.0);
+        assert_eq!(single.drop_position(61).x, 250.0);
+    }
+}
@@ -0,0 +1,169 @@
+use crate::midi;
+use crate::midi::{MidiConfig, MidiInputEvent, Note};
+use crate::settings;
+use crate::settings::Settings;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+pub struct LearnPlugin;
+
+impl Plugin for LearnPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, apply_bindings);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Parameter {
+    TombolaSpin(usize),
+    Bounciness,
+    Gravity,
+    MaxBalls,
+    FixedVelocity,
+    FixedLength,
+    PadNote(usize, usize),
+}
+
+impl Parameter {
+    pub fn default_range(&self) -> (f32, f32) {
+        match self {
+            Parameter::TombolaSpin(_) => (-2.0, 2.0),
+            Parameter::Bounciness => (0.0, 1.0),
+            Parameter::Gravity => (0.0, 1.5),
+            Parameter::MaxBalls => (1.0, 20.0),
+            Parameter::FixedVelocity => (midi::MIN_VELOCITY as f32, midi::MAX_VELOCITY as f32),
+            Parameter::FixedLength => (10.0, 1000.0),
+            Parameter::PadNote(_, _) => (0.0, (midi::NOTES_PER_OCTAVE - 1) as f32),
+        }
+    }
+
+    pub fn without_tombola(self, removed: usize) -> Option<Parameter> {
+        match self {
+            Parameter::TombolaSpin(tombola) => {
+                settings::index_after_removal(tombola, removed).map(Parameter::TombolaSpin)
+            }
+            Parameter::PadNote(tombola, pad) => settings::index_after_removal(tombola, removed)
+                .map(|tombola| Parameter::PadNote(tombola, pad)),
+            parameter => Some(parameter),
+        }
+    }
+
+    fn apply(&self, settings: &mut Settings, value: f32) {
+        match self {
+            Parameter::TombolaSpin(tombola) => {
+                if let Some(tombola) = settings.world.tombolas.get_mut(*tombola) {
+                    tombola.spin = value;
+                }
+            }
+            Parameter::Bounciness => settings.world.bounciness = value.clamp(0.0, 1.0),
+            Parameter::Gravity => settings.world.gravity = value.max(0.0),
+            Parameter::MaxBalls => settings.world.max_balls.limit = value.round().max(1.0) as usize,
+            Parameter::FixedVelocity => {
+                settings.midi.fixed_note_velocity.value = value
+                    .round()
+                    .clamp(midi::MIN_VELOCITY as f32, midi::MAX_VELOCITY as f32)
+                    as u8
+            }
+            Parameter::FixedLength => {
+                settings.midi.fixed_note_length.value = value.round().max(1.0) as u64
+            }
+            Parameter::PadNote(tombola, pad) => {
+                if let Some(pad) = settings
+                    .world
+                    .tombolas
+                    .get_mut(*tombola)
+                    .and_then(|tombola| tombola.pads.get_mut(*pad))
+                {
+                    pad.note = Note::from_index(value.round().max(0.0) as usize);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Parameter::TombolaSpin(tombola) => write!(f, "Tombola {} Spin", tombola + 1),
+            Parameter::Bounciness => write!(f, "Bounciness"),
+            Parameter::Gravity => write!(f, "Gravity"),
+            Parameter::MaxBalls => write!(f, "Max Balls"),
+            Parameter::FixedVelocity => write!(f, "Fixed Note Velocity"),
+            Parameter::FixedLength => write!(f, "Fixed Note Length"),
+            Parameter::PadNote(tombola, pad) => {
+                write!(f, "Tombola {} Pad {} Note", tombola + 1, pad + 1)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MidiBinding {
+    pub parameter: Parameter,
+    pub channel: u8,
+    pub controller: u8,
+    pub range: (f32, f32),
+}
+
+impl MidiBinding {
+    fn new(parameter: Parameter, channel: u8, controller: u8) -> Self {
+        MidiBinding {
+            parameter,
+            channel,
+            controller,
+            range: parameter.default_range(),
+        }
+    }
+
+    fn scale(&self, value: u8) -> f32 {
+        let amount = value as f32 / midi::MAX_CC_VALUE as f32;
+        let (from, to) = self.range;
+
+        from + (to - from) * amount
+    }
+}
+
+fn apply_bindings(
+    mut input: EventReader<MidiInputEvent>,
+    mut config: ResMut<MidiConfig>,
+    mut settings: ResMut<Settings>,
+) {
+    for event in input.read() {
+        if event.message.len() < 3 || event.message[0] & 0xF0 != midi::CC {
+            continue;
+        }
+
+        let channel = (event.message[0] & 0x0F) + midi::MIN_CHANNEL;
+        let controller = event.message[1];
+        let value = event.message[2];
+
+        if controller > midi::MAX_CONTROLLER {
+            continue;
+        }
+
+        if let Some(parameter) = config.learning.take() {
+            settings
+                .midi
+                .bindings
+                .retain(|binding| binding.parameter != parameter);
+            settings
+                .midi
+                .bindings
+                .push(MidiBinding::new(parameter, channel, controller));
+            continue;
+        }
+
+        let updates: Vec<(Parameter, f32)> = settings
+            .midi
+            .bindings
+            .iter()
+            .filter(|binding| binding.channel == channel && binding.controller == controller)
+            .map(|binding| (binding.parameter, binding.scale(value)))
+            .collect();
+
+        for (parameter, value) in updates {
+            parameter.apply(settings.as_mut(), value);
+        }
+    }
+}
@@ -1,14 +1,41 @@
 mod ball;
+mod chord;
+mod collision;
+mod emitter;
 mod geometry;
+mod keyboard;
+mod learn;
 mod midi;
+mod modulation;
+mod mpe;
+mod offline;
+mod osc;
 mod pad;
+mod preset;
+mod recorder;
+mod sampler;
+mod scale;
 mod settings;
 mod size;
+mod smf;
+mod synth;
 mod tombola;
+mod transport;
 mod ui;
+mod velocity;
+mod wav;
 
+use crate::collision::BallCollisionPlugin;
+use crate::emitter::EmitterPlugin;
+use crate::keyboard::KeyboardPlugin;
+use crate::learn::LearnPlugin;
 use crate::midi::MidiConfig;
-use crate::tombola::TombolaPlugin;
+use crate::modulation::ModulationPlugin;
+use crate::mpe::MpePlugin;
+use crate::osc::OscPlugin;
+use crate::sampler::SamplerPlugin;
+use crate::synth::SynthPlugin;
+use crate::tombola::{ShapeEditor, TombolaPlugin};
 use crate::ui::CursorBundle;
 use avian2d::prelude::*;
 use ball::{Ball, BallBundle};
@@ -18,10 +45,15 @@ use bevy::prelude::*;
 use bevy::window::PrimaryWindow;
 use bevy_egui::{EguiContexts, EguiPlugin, EguiPreUpdateSet};
 use midi::MidiPlugin;
+use preset::Presets;
+use recorder::{Recorder, RecorderPlugin};
 use settings::Settings;
-use size::Size;
 use std::cmp::PartialEq;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process;
 use std::time::SystemTime;
+use transport::{Transport, TransportPlugin};
 use ui::{BallSelector, BallSelectorBundle, Highlight, HighlightBundle};
 
 #[derive(Resource, Default)]
@@ -31,7 +63,7 @@ struct WorldMouse {
 
 #[derive(Resource)]
 struct SelectedBall {
-    size: Size,
+    size: usize,
 }
 
 #[derive(Resource, PartialEq)]
@@ -44,8 +76,57 @@ fn get_gravity(gravity_factor: f32) -> Vec2 {
     Vec2::NEG_Y * 700.0 * gravity_factor
 }
 
+fn arg_value(name: &str) -> Option<String> {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == name {
+            return args.next();
+        }
+    }
+
+    None
+}
+
 fn main() {
-    let settings = Settings::default();
+    let mut presets = Presets::default();
+    let settings = match arg_value("--preset").map(PathBuf::from) {
+        Some(path) => match preset::load(&path) {
+            Ok(settings) => {
+                presets.selected = preset::name_of(&path);
+                presets.current = Some(path);
+                settings
+            }
+            Err(error) => {
+                println!("Failed to load preset {}: {}", path.display(), error);
+                Settings::default()
+            }
+        },
+        None => Settings::default(),
+    };
+
+    if let Some(script_path) = arg_value("--render").map(PathBuf::from) {
+        const DEFAULT_SECONDS: f32 = 30.0;
+
+        let output = arg_value("--output").unwrap_or_else(|| "render.mid".to_string());
+        let seconds = arg_value("--seconds")
+            .and_then(|seconds| seconds.parse().ok())
+            .unwrap_or(DEFAULT_SECONDS);
+
+        let script = match offline::load_script(&script_path) {
+            Ok(script) => script,
+            Err(error) => {
+                println!("Failed to load script {}: {}", script_path.display(), error);
+                process::exit(1);
+            }
+        };
+
+        if let Err(error) = offline::render(settings, script, seconds, Path::new(&output)) {
+            println!("Failed to write {}: {}", output, error);
+            process::exit(1);
+        }
+
+        return;
+    }
 
     App::new()
         .add_plugins((
@@ -60,14 +141,24 @@ fn main() {
             EguiPlugin {
                 enable_multipass_for_primary_context: false,
             },
-            MidiPlugin,
+            MidiPlugin::default(),
+            RecorderPlugin,
             TombolaPlugin,
+            TransportPlugin,
+            EmitterPlugin,
+            BallCollisionPlugin,
+            ModulationPlugin,
+            MpePlugin,
+            KeyboardPlugin,
+            LearnPlugin,
+            OscPlugin,
+            (SynthPlugin::default(), SamplerPlugin::default()),
         ))
         .add_systems(
             Startup,
             (
                 setup_camera,
-                spawn_ball_selectors.after(setup_camera),
+                spawn_highlight.after(setup_camera),
                 spawn_cursor,
             ),
         )
@@ -77,7 +168,8 @@ fn main() {
                 update_world_mouse,
                 handle_click.after(EguiPreUpdateSet::InitContexts),
                 handle_scroll,
-                update_selector_positions,
+                update_ball_selectors,
+                update_selector_positions.after(update_ball_selectors),
                 update_highlight.after(update_selector_positions),
                 update_cursor_size,
                 update_cursor_position,
@@ -93,8 +185,9 @@ fn main() {
         .insert_resource(WorldMouse {
             position: Vec2::ZERO,
         })
-        .insert_resource(SelectedBall { size: Size::Small })
+        .insert_resource(SelectedBall { size: 0 })
         .insert_resource(settings)
+        .insert_resource(presets)
         .insert_resource(DragState::NotDragging)
         .run();
 }
@@ -119,7 +212,13 @@ fn get_ball_selector_x(window: &Window) -> f32 {
     window.width() / 2.0 - SPACING
 }
 
-fn spawn_ball_selectors(
+fn get_ball_selector_y(window: &Window, index: usize, count: usize) -> f32 {
+    const SPACING: f32 = 100.0;
+    let spacing = SPACING.min(window.height() / (count + 1) as f32);
+    ((count as f32 - 1.0) / 2.0 - index as f32) * spacing
+}
+
+fn spawn_highlight(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
@@ -132,27 +231,38 @@ fn spawn_ball_selectors(
         &mut meshes,
         &mut materials,
     ));
+}
 
-    commands.spawn(BallSelectorBundle::new(
-        Size::Small,
-        Vec2::new(x_pos, 100.0),
-        &mut meshes,
-        &mut materials,
-    ));
-
-    commands.spawn(BallSelectorBundle::new(
-        Size::Medium,
-        Vec2::new(x_pos, 0.0),
-        &mut meshes,
-        &mut materials,
-    ));
+fn update_ball_selectors(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    selectors: Query<(Entity, &BallSelector)>,
+    settings: Res<Settings>,
+) {
+    let palette = &settings.world.palette;
+    let is_stale = selectors.iter().count() != palette.len()
+        || selectors.iter().any(|(_, selector)| {
+            palette
+                .get(selector.size)
+                .is_none_or(|ball_size| !selector.matches(ball_size))
+        });
+
+    if is_stale {
+        for (entity, _) in selectors.iter() {
+            commands.entity(entity).despawn();
+        }
 
-    commands.spawn(BallSelectorBundle::new(
-        Size::Large,
-        Vec2::new(x_pos, -100.0),
-        &mut meshes,
-        &mut materials,
-    ));
+        for (index, ball_size) in palette.iter().enumerate() {
+            commands.spawn(BallSelectorBundle::new(
+                index,
+                ball_size,
+                Vec2::ZERO,
+                &mut meshes,
+                &mut materials,
+            ));
+        }
+    }
 }
 
 fn spawn_cursor(
@@ -160,9 +270,11 @@ fn spawn_cursor(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     selected_ball: Res<SelectedBall>,
+    settings: Res<Settings>,
 ) {
     commands.spawn(CursorBundle::new(
         selected_ball.size,
+        &settings.world.ball_size(selected_ball.size),
         Vec2::default(),
         &mut meshes,
         &mut materials,
@@ -185,13 +297,16 @@ fn update_world_mouse(
 }
 
 fn update_selector_positions(
-    mut selectors: Query<&mut Transform, With<BallSelector>>,
+    mut selectors: Query<(&BallSelector, &mut Transform)>,
     window: Query<&Window, With<PrimaryWindow>>,
 ) {
-    let x_pos = get_ball_selector_x(window.single().unwrap());
+    let window = window.single().unwrap();
+    let x_pos = get_ball_selector_x(window);
+    let count = selectors.iter().count();
 
-    for mut selector in selectors.iter_mut() {
-        selector.translation.x = x_pos;
+    for (selector, mut transform) in selectors.iter_mut() {
+        transform.translation.x = x_pos;
+        transform.translation.y = get_ball_selector_y(window, selector.size, count);
     }
 }
 
@@ -256,13 +371,15 @@ fn update_cursor_size(
         &mut MeshMaterial2d<ColorMaterial>,
     )>,
     selected_ball: Res<SelectedBall>,
+    settings: Res<Settings>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
     if let Ok((mut cursor, mut mesh, mut material)) = cursors.single_mut() {
+        let ball_size = settings.world.ball_size(selected_ball.size);
         cursor.size = selected_ball.size;
-        *mesh = cursor.get_mesh(&mut meshes);
-        *material = cursor.get_material(&mut materials);
+        *mesh = ui::Cursor::get_mesh(&ball_size, &mut meshes);
+        *material = ui::Cursor::get_material(&ball_size, &mut materials);
     }
 }
 
@@ -273,6 +390,7 @@ fn draw_drag_arrow(mut gizmos: Gizmos, drag_state: Res<DragState>, world_mouse:
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn handle_click(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
@@ -280,16 +398,44 @@ fn handle_click(
     mut selected_ball: ResMut<SelectedBall>,
     mut settings: ResMut<Settings>,
     mut midi_config: ResMut<MidiConfig>,
+    mut presets: ResMut<Presets>,
+    mut recorder: ResMut<Recorder>,
+    mut transport: ResMut<Transport>,
+    mut shape_editor: ResMut<ShapeEditor>,
     mut drag_state: ResMut<DragState>,
     world_mouse: Res<WorldMouse>,
     buttons: Res<ButtonInput<MouseButton>>,
     selectors: Query<(&BallSelector, &Transform)>,
-    balls: Query<(Entity, &Ball)>,
+    mut balls: Query<(Entity, &mut Ball)>,
     egui: EguiContexts,
 ) {
-    let handled = ui::show_settings_menu(egui, settings.as_mut(), midi_config.as_mut());
-    if handled {
+    let menu = ui::show_settings_menu(
+        egui,
+        settings.as_mut(),
+        midi_config.as_mut(),
+        presets.as_mut(),
+        recorder.as_mut(),
+        transport.as_mut(),
+        shape_editor.as_mut(),
+    );
+    if let Some(index) = menu.removed_ball_size {
+        for (entity, mut ball) in balls.iter_mut() {
+            match settings::index_after_removal(ball.size, index) {
+                Some(size) => ball.size = size,
+                None => commands.entity(entity).despawn(),
+            }
+        }
+        selected_ball.size =
+            settings::index_after_removal(selected_ball.size, index).unwrap_or_default();
+    }
+    if menu.hovered {
+        *drag_state = DragState::NotDragging;
+        return;
+    }
+
+    if shape_editor.is_editing() {
         *drag_state = DragState::NotDragging;
+        shape_editor.update(settings.as_mut(), world_mouse.position, &buttons);
         return;
     }
 
@@ -317,7 +463,7 @@ fn handle_click(
                 pos,
                 drag_vector * DRAG_POWER,
                 selected_ball.size,
-                settings.world.bounciness,
+                &settings.world,
                 &mut meshes,
                 &mut materials,
             ));
@@ -327,30 +473,50 @@ fn handle_click(
     }
 }
 
-fn handle_scroll(mut scrolls: EventReader<MouseWheel>, mut selected_ball: ResMut<SelectedBall>) {
+fn handle_scroll(
+    mut scrolls: EventReader<MouseWheel>,
+    mut selected_ball: ResMut<SelectedBall>,
+    settings: Res<Settings>,
+) {
+    let last = settings.world.palette.len().saturating_sub(1);
+
     for event in scrolls.read() {
         match event.unit {
             MouseScrollUnit::Line => {
                 if event.y < -0.099 {
-                    selected_ball.size = selected_ball.size.increment();
+                    selected_ball.size = (selected_ball.size + 1).min(last);
                 } else if event.y > 0.099 {
-                    selected_ball.size = selected_ball.size.decrement();
+                    selected_ball.size = selected_ball.size.saturating_sub(1);
                 }
             }
             MouseScrollUnit::Pixel => {
                 if event.y < -50.0 {
-                    selected_ball.size = selected_ball.size.increment();
+                    selected_ball.size = (selected_ball.size + 1).min(last);
                 } else if event.y > 50.0 {
-                    selected_ball.size = selected_ball.size.decrement();
+                    selected_ball.size = selected_ball.size.saturating_sub(1);
                 }
             }
         }
     }
+
+    selected_ball.size = selected_ball.size.min(last);
 }
 
-fn update_bounciness(mut bouncy_things: Query<&mut Restitution>, settings: Res<Settings>) {
-    for mut thing in bouncy_things.iter_mut() {
-        thing.coefficient = settings.world.bounciness;
+fn update_bounciness(
+    mut bouncy_things: Query<(&mut Restitution, Option<&Ball>)>,
+    settings: Res<Settings>,
+) {
+    for (mut thing, ball) in bouncy_things.iter_mut() {
+        thing.coefficient = match ball {
+            Some(ball) => settings
+                .world
+                .palette
+                .get(ball.size)
+                .map_or(settings.world.bounciness, |ball_size| {
+                    ball_size.restitution(settings.world.bounciness)
+                }),
+            None => settings.world.bounciness,
+        };
     }
 }
 
@@ -358,14 +524,14 @@ fn update_gravity(mut gravity: ResMut<Gravity>, settings: Res<Settings>) {
     gravity.0 = get_gravity(settings.world.gravity);
 }
 
-fn oldest_ball(balls: &Vec<(Entity, SystemTime)>) -> Option<Entity> {
+fn oldest_ball(balls: &[(Entity, SystemTime)]) -> Option<Entity> {
     balls
         .iter()
         .max_by(|a, b| {
             let (_, a_spawn_time) = a;
             let (_, b_spawn_time) = b;
 
-            b_spawn_time.cmp(&a_spawn_time)
+            b_spawn_time.cmp(a_spawn_time)
         })
         .map(|(entity, _)| *entity)
 }
@@ -394,9 +560,14 @@ fn clean_up_balls(
     window: Query<&Window, With<PrimaryWindow>>,
     settings: Res<Settings>,
 ) {
-    let window = window.single().unwrap();
-    let half_width = window.width() / 2.0;
-    let half_height = window.height() / 2.0;
+    const OFFLINE_WORLD_SIZE: Vec2 = Vec2::new(1280.0, 720.0);
+
+    let world_size = window
+        .single()
+        .map(|window| window.size())
+        .unwrap_or(OFFLINE_WORLD_SIZE);
+    let half_width = world_size.x / 2.0;
+    let half_height = world_size.y / 2.0;
     let rect = Rect::new(-half_width, -half_height, half_width, half_height);
 
     for (entity, ball, transform) in balls.iter_mut() {
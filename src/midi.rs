@@ -1,12 +1,50 @@
+use crate::learn::Parameter;
+use crate::mpe;
+use crate::mpe::MpeVoices;
+use crate::recorder::Recorder;
+use crate::settings::Settings;
 use bevy::prelude::*;
-use midir::{MidiOutput, MidiOutputConnection, MidiOutputPort};
+#[cfg(target_os = "linux")]
+use midir::os::unix::VirtualOutput;
+use midir::{
+    MidiInput, MidiInputConnection, MidiInputPort, MidiOutput, MidiOutputConnection, MidiOutputPort,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
-const NOTE_ON_MSG: u8 = 0x90;
-const NOTE_OFF_MSG: u8 = 0x80;
-const CC: u8 = 0xB0;
+pub const NOTE_ON_MSG: u8 = 0x90;
+pub const NOTE_OFF_MSG: u8 = 0x80;
+pub const CC: u8 = 0xB0;
+const CHANNEL_PRESSURE: u8 = 0xD0;
+const PITCH_BEND: u8 = 0xE0;
 const PANIC: u8 = 0x7B;
+pub const CLOCK: u8 = 0xF8;
+pub const START: u8 = 0xFA;
+pub const CONTINUE: u8 = 0xFB;
+pub const STOP: u8 = 0xFC;
+
+pub const VIRTUAL_PORT_NAME: &str = "Tombola (Virtual)";
+const VIRTUAL_CLIENT_NAME: &str = "Tombola";
+const VIRTUAL_OUTPUT_NAME: &str = "Tombola Out";
+
+pub const CLOCKS_PER_QUARTER: u32 = 24;
+
+pub const NOTES_PER_OCTAVE: usize = 12;
+pub const MIN_PITCH: u8 = 0;
+pub const MAX_PITCH: u8 = 127;
+
+pub const MAX_CONTROLLER: u8 = 119;
+pub const MAX_CC_VALUE: u8 = 127;
+
+pub const MIN_VELOCITY: u8 = 1;
+pub const MAX_VELOCITY: u8 = 127;
+
+pub const MIN_CHANNEL: u8 = 1;
+pub const MAX_CHANNEL: u8 = 16;
 
 const C3: u8 = 0x3C;
 const C_SHARP3: u8 = 0x3D;
@@ -21,18 +59,47 @@ const A3: u8 = 0x45;
 const A_SHARP3: u8 = 0x46;
 const B3: u8 = 0x47;
 
-pub struct MidiPlugin;
+#[derive(Default)]
+pub struct MidiPlugin {
+    pub offline: bool,
+}
 
 impl Plugin for MidiPlugin {
     fn build(&self, app: &mut App) {
+        app.add_event::<MidiOutputEvent>();
+        app.add_event::<MidiInputEvent>();
+        app.init_resource::<MpeVoices>();
+        app.add_systems(
+            Update,
+            (configure_mpe, process_output_events.after(configure_mpe)),
+        );
+
+        if self.offline {
+            return;
+        }
+
         app.insert_resource(MidiHandle(None));
+        app.insert_resource(MidiInputHandle {
+            connection: Mutex::new(None),
+            port_name: String::new(),
+            messages: Arc::new(Mutex::new(Vec::new())),
+        });
         app.insert_resource(MidiConfig {
             active_port: String::new(),
-            port_watcher: MidiOutput::new("port_watcher").unwrap(),
+            active_input_port: String::new(),
+            learning: None,
+            port_watcher: Mutex::new(MidiOutput::new("port_watcher").unwrap()),
+            input_port_watcher: Mutex::new(MidiInput::new("input_port_watcher").unwrap()),
         });
-        app.add_event::<MidiOutputEvent>();
         app.add_systems(Startup, connect_to_default_output_port);
-        app.add_systems(Update, (process_output_events, update_midi_connection));
+        app.add_systems(
+            Update,
+            (
+                update_midi_connection,
+                update_midi_input_connection,
+                receive_input_messages.after(update_midi_input_connection),
+            ),
+        );
     }
 }
 
@@ -42,13 +109,19 @@ pub struct Port {
 }
 
 struct MidiConnection {
-    pub connection: MidiOutputConnection,
+    pub connection: Mutex<MidiOutputConnection>,
     pub port_name: String,
 }
 
 impl Drop for MidiConnection {
     fn drop(&mut self) {
-        let _ = self.connection.send(&[CC, PANIC, 0]);
+        for channel in MIN_CHANNEL..=MAX_CHANNEL {
+            let _ = self
+                .connection
+                .lock()
+                .unwrap()
+                .send(&[status(CC, channel), PANIC, 0]);
+        }
     }
 }
 
@@ -56,43 +129,150 @@ impl Drop for MidiConnection {
 struct MidiHandle(Option<MidiConnection>);
 
 impl MidiHandle {
-    pub fn connect_to(&mut self, port: &Port) {
+    pub fn connect_to(&mut self, port: &Port) -> bool {
         let output = MidiOutput::new("Output").unwrap();
         if let Ok(connection) = output.connect(&port.port, "Connection") {
             self.0 = Some(MidiConnection {
-                connection,
+                connection: Mutex::new(connection),
                 port_name: port.name.clone(),
             });
             println!("Connected to {}", port.name);
+            true
+        } else {
+            println!("Failed to connect to {}", port.name);
+            false
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn connect_to_virtual(&mut self) -> bool {
+        let output = MidiOutput::new(VIRTUAL_CLIENT_NAME).unwrap();
+        if let Ok(connection) = output.create_virtual(VIRTUAL_OUTPUT_NAME) {
+            self.0 = Some(MidiConnection {
+                connection: Mutex::new(connection),
+                port_name: VIRTUAL_PORT_NAME.to_string(),
+            });
+            println!("Created virtual port {}", VIRTUAL_OUTPUT_NAME);
+            true
+        } else {
+            println!("Failed to create virtual port {}", VIRTUAL_OUTPUT_NAME);
+            false
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn connect_to_virtual(&mut self) -> bool {
+        println!("Virtual MIDI ports are only supported on Linux");
+        false
+    }
+}
+
+pub struct InputPort {
+    pub name: String,
+    pub port: MidiInputPort,
+}
+
+type InputMessages = Arc<Mutex<Vec<(u64, Vec<u8>)>>>;
+
+#[derive(Resource)]
+struct MidiInputHandle {
+    connection: Mutex<Option<MidiInputConnection<()>>>,
+    port_name: String,
+    messages: InputMessages,
+}
+
+impl MidiInputHandle {
+    pub fn connect_to(&mut self, port: &InputPort) {
+        self.disconnect();
+
+        let input = MidiInput::new("Input").unwrap();
+        let messages = self.messages.clone();
+        let connection = input.connect(
+            &port.port,
+            "Connection",
+            move |timestamp, message, _| {
+                if let Ok(mut messages) = messages.lock() {
+                    messages.push((timestamp, message.to_vec()));
+                }
+            },
+            (),
+        );
+
+        self.port_name = port.name.clone();
+        if let Ok(connection) = connection {
+            *self.connection.lock().unwrap() = Some(connection);
+            println!("Connected to input {}", port.name);
         } else {
-            println!("Failed to connect to {}", port.name)
+            println!("Failed to connect to input {}", port.name)
         }
     }
+
+    pub fn disconnect(&mut self) {
+        *self.connection.lock().unwrap() = None;
+        self.port_name = String::new();
+    }
+}
+
+#[derive(Event)]
+pub struct MidiInputEvent {
+    pub timestamp: u64,
+    pub message: Vec<u8>,
 }
 
 #[derive(Resource)]
 pub struct MidiConfig {
     pub active_port: String,
-    port_watcher: MidiOutput,
+    pub active_input_port: String,
+    pub learning: Option<Parameter>,
+    port_watcher: Mutex<MidiOutput>,
+    input_port_watcher: Mutex<MidiInput>,
 }
 
 impl MidiConfig {
+    pub fn get_port_names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        if cfg!(target_os = "linux") {
+            names.push(VIRTUAL_PORT_NAME.to_string());
+        }
+        names.extend(self.get_ports().into_iter().map(|port| port.name));
+
+        names
+    }
+
     pub fn get_ports(&self) -> Vec<Port> {
         let mut ports = Vec::new();
-        for port in self.port_watcher.ports() {
-            if let Ok(name) = self.port_watcher.port_name(&port) {
+        let watcher = self.port_watcher.lock().unwrap();
+        for port in watcher.ports() {
+            if let Ok(name) = watcher.port_name(&port) {
                 ports.push(Port { port, name });
             }
         }
 
         ports
     }
+
+    pub fn get_input_ports(&self) -> Vec<InputPort> {
+        let mut ports = Vec::new();
+        let watcher = self.input_port_watcher.lock().unwrap();
+        for port in watcher.ports() {
+            if let Ok(name) = watcher.port_name(&port) {
+                ports.push(InputPort { port, name });
+            }
+        }
+
+        ports
+    }
 }
 
 fn connect_to_default_output_port(mut midi: ResMut<MidiHandle>, mut config: ResMut<MidiConfig>) {
+    if cfg!(target_os = "linux") && midi.connect_to_virtual() {
+        config.active_port = VIRTUAL_PORT_NAME.to_string();
+        return;
+    }
+
     let ports = config.get_ports();
-    if let Some(default_port) = ports.get(0) {
-        midi.connect_to(&default_port);
+    if let Some(default_port) = ports.first() {
+        midi.connect_to(default_port);
         config.active_port = default_port.name.clone();
     } else {
         config.active_port = String::new();
@@ -100,30 +280,61 @@ fn connect_to_default_output_port(mut midi: ResMut<MidiHandle>, mut config: ResM
     }
 }
 
-fn update_midi_connection(config: Res<MidiConfig>, mut handle: ResMut<MidiHandle>) {
+fn update_midi_connection(mut config: ResMut<MidiConfig>, mut handle: ResMut<MidiHandle>) {
     if config.active_port.is_empty() {
         return;
     }
 
-    if let Some(connection) = &mut handle.0 {
-        if connection.port_name != config.active_port {
-            if let Some(port) = config
-                .get_ports()
-                .iter()
-                .find(|port| port.name == config.active_port)
-            {
-                handle.connect_to(port);
-            }
-        }
+    let current = handle
+        .0
+        .as_ref()
+        .map(|connection| connection.port_name.clone());
+    if current.as_ref() == Some(&config.active_port) {
+        return;
+    }
+
+    let connected = if config.active_port == VIRTUAL_PORT_NAME {
+        handle.connect_to_virtual()
+    } else if let Some(port) = config
+        .get_ports()
+        .iter()
+        .find(|port| port.name == config.active_port)
+    {
+        handle.connect_to(port)
     } else {
-        println!(
-            "No current connection, connecting to port {}",
-            config.active_port
-        );
+        false
+    };
+
+    if !connected {
+        config.active_port = current.unwrap_or_default();
+    }
+}
+
+fn update_midi_input_connection(config: Res<MidiConfig>, mut handle: ResMut<MidiInputHandle>) {
+    if handle.port_name == config.active_input_port {
+        return;
+    }
+
+    if config.active_input_port.is_empty() {
+        handle.disconnect();
+    } else if let Some(port) = config
+        .get_input_ports()
+        .iter()
+        .find(|port| port.name == config.active_input_port)
+    {
+        handle.connect_to(port);
     }
 }
 
-#[derive(Clone, Copy, PartialEq, EnumIter)]
+fn receive_input_messages(handle: Res<MidiInputHandle>, mut events: EventWriter<MidiInputEvent>) {
+    if let Ok(mut messages) = handle.messages.lock() {
+        for (timestamp, message) in messages.drain(..) {
+            events.write(MidiInputEvent { timestamp, message });
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, EnumIter, Serialize, Deserialize)]
 pub enum Note {
     C,
     CSharp,
@@ -140,9 +351,8 @@ pub enum Note {
 }
 
 impl Note {
-    fn to_value(&self, octave: i32) -> u8 {
+    pub fn to_value(self, octave: i32) -> i32 {
         const BASE_OCTAVE: i32 = 3;
-        const NOTES_PER_OCTAVE: i32 = 12;
 
         let base_note = match self {
             Note::C => C3,
@@ -160,74 +370,242 @@ impl Note {
         };
 
         let shift = octave - BASE_OCTAVE;
-        (base_note as i32 + (shift * NOTES_PER_OCTAVE)) as u8
+        base_note as i32 + (shift * NOTES_PER_OCTAVE as i32)
+    }
+
+    pub fn to_index(self) -> usize {
+        Note::iter()
+            .position(|note| note == self)
+            .unwrap_or_default()
+    }
+
+    pub fn from_index(index: usize) -> Note {
+        Note::iter()
+            .nth(index % NOTES_PER_OCTAVE)
+            .unwrap_or(Note::C)
     }
 
-    pub fn to_string(&self) -> String {
+    pub fn transpose(&self, semitones: i32) -> Note {
+        let index = self.to_index() as i32 + semitones;
+        Note::from_index(index.rem_euclid(NOTES_PER_OCTAVE as i32) as usize)
+    }
+}
+
+impl fmt::Display for Note {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Note::C => "C".to_owned(),
-            Note::CSharp => "C#".to_owned(),
-            Note::D => "D".to_owned(),
-            Note::DSharp => "Eb".to_owned(),
-            Note::E => "E".to_owned(),
-            Note::F => "F".to_owned(),
-            Note::FSharp => "F#".to_owned(),
-            Note::G => "G".to_owned(),
-            Note::GSharp => "G#".to_owned(),
-            Note::A => "A".to_owned(),
-            Note::ASharp => "Bb".to_owned(),
-            Note::B => "B".to_owned(),
+            Note::C => write!(f, "C"),
+            Note::CSharp => write!(f, "C#"),
+            Note::D => write!(f, "D"),
+            Note::DSharp => write!(f, "Eb"),
+            Note::E => write!(f, "E"),
+            Note::F => write!(f, "F"),
+            Note::FSharp => write!(f, "F#"),
+            Note::G => write!(f, "G"),
+            Note::GSharp => write!(f, "G#"),
+            Note::A => write!(f, "A"),
+            Note::ASharp => write!(f, "Bb"),
+            Note::B => write!(f, "B"),
         }
     }
 }
 
-#[derive(Event)]
+#[derive(Clone, Copy, PartialEq, EnumIter, Serialize, Deserialize)]
+pub enum OutOfRange {
+    Clamp,
+    Fold,
+}
+
+impl OutOfRange {
+    pub fn apply(&self, value: i32) -> u8 {
+        let value = match self {
+            OutOfRange::Clamp => value,
+            OutOfRange::Fold => {
+                let octave = NOTES_PER_OCTAVE as i32;
+                let mut value = value;
+                while value < MIN_PITCH as i32 {
+                    value += octave;
+                }
+                while value > MAX_PITCH as i32 {
+                    value -= octave;
+                }
+                value
+            }
+        };
+
+        value.clamp(MIN_PITCH as i32, MAX_PITCH as i32) as u8
+    }
+}
+
+impl fmt::Display for OutOfRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OutOfRange::Clamp => write!(f, "Clamp"),
+            OutOfRange::Fold => write!(f, "Fold"),
+        }
+    }
+}
+
+pub fn pitch_name(pitch: u8) -> String {
+    let octave = pitch as i32 / NOTES_PER_OCTAVE as i32 - 2;
+    format!("{}{}", Note::from_index(pitch as usize), octave)
+}
+
+pub fn status(message: u8, channel: u8) -> u8 {
+    let channel = channel.clamp(MIN_CHANNEL, MAX_CHANNEL) - MIN_CHANNEL;
+    message | channel
+}
+
+#[derive(Event, Clone, PartialEq)]
 pub enum MidiOutputEvent {
     NoteOn {
-        note: Note,
-        octave: i32,
+        pitch: u8,
         velocity: u8,
+        channel: u8,
+        ball: Option<Entity>,
     },
     NoteOff {
-        note: Note,
-        octave: i32,
+        pitch: u8,
+        channel: u8,
+    },
+    ControlChange {
+        controller: u8,
+        value: u8,
+        channel: u8,
     },
+    PitchBend {
+        value: u16,
+        channel: u8,
+    },
+    ChannelPressure {
+        value: u8,
+        channel: u8,
+    },
+    Clock,
+    Start,
+    Continue,
+    Stop,
+}
+
+impl MidiOutputEvent {
+    pub fn to_message(&self) -> Vec<u8> {
+        match self {
+            MidiOutputEvent::NoteOn {
+                pitch,
+                velocity,
+                channel,
+                ..
+            } => vec![status(NOTE_ON_MSG, *channel), *pitch, *velocity],
+            MidiOutputEvent::NoteOff { pitch, channel } => {
+                vec![status(NOTE_OFF_MSG, *channel), *pitch, 0x7F]
+            }
+            MidiOutputEvent::ControlChange {
+                controller,
+                value,
+                channel,
+            } => vec![status(CC, *channel), *controller, *value],
+            MidiOutputEvent::PitchBend { value, channel } => vec![
+                status(PITCH_BEND, *channel),
+                (*value & 0x7F) as u8,
+                ((*value >> 7) & 0x7F) as u8,
+            ],
+            MidiOutputEvent::ChannelPressure { value, channel } => {
+                vec![status(CHANNEL_PRESSURE, *channel), *value]
+            }
+            MidiOutputEvent::Clock => vec![CLOCK],
+            MidiOutputEvent::Start => vec![START],
+            MidiOutputEvent::Continue => vec![CONTINUE],
+            MidiOutputEvent::Stop => vec![STOP],
+        }
+    }
 }
 
-fn process_output_events(mut events: EventReader<MidiOutputEvent>, mut midi: ResMut<MidiHandle>) {
+fn process_output_events(
+    mut events: EventReader<MidiOutputEvent>,
+    mut midi: Option<ResMut<MidiHandle>>,
+    mut recorder: ResMut<Recorder>,
+    mut voices: ResMut<MpeVoices>,
+    settings: Res<Settings>,
+) {
+    let mpe = &settings.midi.mpe;
+
     for event in events.read() {
-        if let Some(handle) = &mut midi.0 {
-            match event {
-                MidiOutputEvent::NoteOn {
-                    note,
-                    octave,
-                    velocity,
-                } => {
-                    let _ =
-                        handle
-                            .connection
-                            .send(&[NOTE_ON_MSG, note.to_value(*octave), *velocity]);
-                }
-                MidiOutputEvent::NoteOff { note, octave } => {
-                    let _ = handle
-                        .connection
-                        .send(&[NOTE_OFF_MSG, note.to_value(*octave), 0x7F]);
+        let routed = match event {
+            MidiOutputEvent::NoteOn {
+                pitch,
+                velocity,
+                channel,
+                ball,
+            } if mpe.enabled => {
+                let allocation = voices.note_on(mpe, *pitch, *channel, *ball);
+                let mut routed: Vec<MidiOutputEvent> = allocation
+                    .stolen
+                    .iter()
+                    .map(|pitch| MidiOutputEvent::NoteOff {
+                        pitch: *pitch,
+                        channel: allocation.member,
+                    })
+                    .collect();
+                if allocation.allocated {
+                    routed.extend(mpe::initial_expression(allocation.member));
                 }
+                routed.push(MidiOutputEvent::NoteOn {
+                    pitch: *pitch,
+                    velocity: *velocity,
+                    channel: allocation.member,
+                    ball: *ball,
+                });
+                routed
+            }
+            MidiOutputEvent::NoteOff { pitch, channel } => vec![MidiOutputEvent::NoteOff {
+                pitch: *pitch,
+                channel: voices.note_off(*pitch, *channel).unwrap_or(*channel),
+            }],
+            event => vec![event.clone()],
+        };
+
+        for event in routed {
+            let message = event.to_message();
+            recorder.record(&message);
+
+            if !settings.synth.output.sends_midi() {
+                continue;
+            }
+
+            if let Some(handle) = midi.as_mut().and_then(|midi| midi.0.as_mut()) {
+                let _ = handle.connection.lock().unwrap().send(&message);
             }
         }
     }
 }
 
-pub fn to_velocity(speed: f32) -> u8 {
-    const MAX_SPEED: f32 = 750.0;
-    const MIN_SPEED: f32 = 50.0;
+fn configure_mpe(
+    midi: Option<Res<MidiHandle>>,
+    settings: Res<Settings>,
+    mut configured: Local<Option<(String, Vec<MidiOutputEvent>)>>,
+    mut events: EventWriter<MidiOutputEvent>,
+) {
+    let port_name = midi
+        .as_ref()
+        .and_then(|midi| midi.0.as_ref())
+        .map(|handle| handle.port_name.clone())
+        .unwrap_or_default();
+    let mpe = &settings.midi.mpe;
+    let configuration = mpe.configuration();
 
-    let scaled0to1 = (speed.clamp(MIN_SPEED, MAX_SPEED) - MIN_SPEED) / (MAX_SPEED - MIN_SPEED);
+    if let Some((name, previous)) = configured.as_ref() {
+        if *name == port_name && *previous == configuration {
+            return;
+        }
+    }
 
-    const MAX_MIDI_VEL: u8 = 0x7F;
-    let scaled = MAX_MIDI_VEL as f32 * scaled0to1;
+    if mpe.enabled || configured.is_some() {
+        for event in configuration.iter() {
+            events.write(event.clone());
+        }
+    }
 
-    scaled as u8
+    *configured = Some((port_name, configuration));
 }
 
 pub fn to_note_duration(speed: f32) -> Duration {
@@ -0,0 +1,262 @@
+use crate::ball::Ball;
+use crate::midi;
+use crate::midi::MidiOutputEvent;
+use crate::pad::Pad;
+use crate::settings::Settings;
+use crate::tombola::Tombola;
+use crate::transport::Transport;
+use crate::velocity::Curve;
+use avian2d::math::TAU;
+use avian2d::prelude::{CollisionStarted, LinearVelocity};
+use bevy::math::ops::acos;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+use strum_macros::EnumIter;
+
+pub const MIN_RATE: f32 = 1.0;
+pub const MAX_RATE: f32 = 100.0;
+
+const MAX_PITCH_BEND: u16 = 0x3FFF;
+
+pub struct ModulationPlugin;
+
+impl Plugin for ModulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ModulationState>();
+        app.add_systems(
+            Update,
+            (track_impacts, send_modulation.after(track_impacts)),
+        );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, EnumIter, Serialize, Deserialize)]
+pub enum Source {
+    BallX,
+    BallY,
+    Speed,
+    ImpactAngle,
+    Bounces,
+    TombolaAngle,
+    BallCount,
+}
+
+impl Source {
+    pub fn default_range(&self) -> (f32, f32) {
+        match self {
+            Source::BallX | Source::BallY => (-500.0, 500.0),
+            Source::Speed => (0.0, 1000.0),
+            Source::ImpactAngle => (0.0, 90.0),
+            Source::Bounces => (0.0, 20.0),
+            Source::TombolaAngle => (0.0, 360.0),
+            Source::BallCount => (0.0, 50.0),
+        }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::BallX => write!(f, "Ball X"),
+            Source::BallY => write!(f, "Ball Y"),
+            Source::Speed => write!(f, "Ball Speed"),
+            Source::ImpactAngle => write!(f, "Impact Angle"),
+            Source::Bounces => write!(f, "Bounces"),
+            Source::TombolaAngle => write!(f, "Tombola Angle"),
+            Source::BallCount => write!(f, "Ball Count"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, EnumIter, Serialize, Deserialize)]
+pub enum Destination {
+    ControlChange,
+    PitchBend,
+}
+
+impl fmt::Display for Destination {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Destination::ControlChange => write!(f, "Control Change"),
+            Destination::PitchBend => write!(f, "Pitch Bend"),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModulationSettings {
+    pub enabled: bool,
+    pub source: Source,
+    pub tombola: usize,
+    pub range: (f32, f32),
+    pub curve: Curve,
+    pub destination: Destination,
+    pub controller: u8,
+    pub channel: u8,
+    pub output: (f32, f32),
+}
+
+impl Default for ModulationSettings {
+    fn default() -> Self {
+        ModulationSettings {
+            enabled: true,
+            source: Source::BallX,
+            tombola: 0,
+            range: Source::BallX.default_range(),
+            curve: Curve::Linear,
+            destination: Destination::ControlChange,
+            controller: 74,
+            channel: midi::MIN_CHANNEL,
+            output: (0.0, 1.0),
+        }
+    }
+}
+
+impl ModulationSettings {
+    fn to_amount(&self, value: f32) -> f32 {
+        let (from, to) = self.range;
+        let span = to - from;
+        let amount = if span.abs() < f32::EPSILON {
+            0.0
+        } else {
+            (value - from) / span
+        };
+
+        let (min, max) = self.output;
+        min + (max - min) * self.curve.apply(amount)
+    }
+
+    fn to_event(&self, value: f32) -> MidiOutputEvent {
+        let amount = self.to_amount(value).clamp(0.0, 1.0);
+
+        match self.destination {
+            Destination::ControlChange => MidiOutputEvent::ControlChange {
+                controller: self.controller.min(midi::MAX_CONTROLLER),
+                value: (amount * midi::MAX_CC_VALUE as f32).round() as u8,
+                channel: self.channel,
+            },
+            Destination::PitchBend => MidiOutputEvent::PitchBend {
+                value: (amount * MAX_PITCH_BEND as f32).round() as u16,
+                channel: self.channel,
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Modulation {
+    pub rate: f32,
+    pub mappings: Vec<ModulationSettings>,
+}
+
+impl Default for Modulation {
+    fn default() -> Self {
+        Modulation {
+            rate: 20.0,
+            mappings: Vec::new(),
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct ModulationState {
+    elapsed: Duration,
+    impact_angle: f32,
+    sent: HashMap<usize, MidiOutputEvent>,
+}
+
+fn track_impacts(
+    mut collisions: EventReader<CollisionStarted>,
+    mut state: ResMut<ModulationState>,
+    balls: Query<&LinearVelocity, With<Ball>>,
+    pads: Query<&GlobalTransform, With<Pad>>,
+) {
+    for CollisionStarted(first, second) in collisions.read() {
+        let pair = if let (Ok(velocity), Ok(pad)) = (balls.get(*first), pads.get(*second)) {
+            Some((velocity, pad))
+        } else if let (Ok(velocity), Ok(pad)) = (balls.get(*second), pads.get(*first)) {
+            Some((velocity, pad))
+        } else {
+            None
+        };
+
+        if let Some((velocity, pad)) = pair {
+            let normal = (pad.rotation() * Vec3::Y).truncate();
+            if let Some(direction) = velocity.0.try_normalize() {
+                let angle = acos(direction.dot(normal).abs().clamp(0.0, 1.0));
+                state.impact_angle = 90.0 - angle.to_degrees();
+            }
+        }
+    }
+}
+
+fn send_modulation(
+    mut state: ResMut<ModulationState>,
+    balls: Query<(&Ball, &Transform, &LinearVelocity)>,
+    tombolas: Query<(&Tombola, &Transform)>,
+    settings: Res<Settings>,
+    transport: Res<Transport>,
+    time: Res<Time>,
+    mut midi: EventWriter<MidiOutputEvent>,
+) {
+    let modulation = &settings.midi.modulation;
+
+    if !transport.is_playing() {
+        state.sent.clear();
+        return;
+    }
+
+    state.elapsed += time.delta();
+    let interval = Duration::from_secs_f32(1.0 / modulation.rate.clamp(MIN_RATE, MAX_RATE));
+    if state.elapsed < interval {
+        return;
+    }
+    state.elapsed = Duration::ZERO;
+
+    let count = balls.iter().count();
+    let average = |value: &dyn Fn(&Ball, &Transform, &LinearVelocity) -> f32| {
+        if count == 0 {
+            return 0.0;
+        }
+
+        balls
+            .iter()
+            .map(|(ball, transform, velocity)| value(ball, transform, velocity))
+            .sum::<f32>()
+            / count as f32
+    };
+
+    for (index, mapping) in modulation.mappings.iter().enumerate() {
+        if !mapping.enabled {
+            continue;
+        }
+
+        let value = match mapping.source {
+            Source::BallX => average(&|_, transform, _| transform.translation.x),
+            Source::BallY => average(&|_, transform, _| transform.translation.y),
+            Source::Speed => average(&|_, _, velocity| velocity.length()),
+            Source::ImpactAngle => state.impact_angle,
+            Source::Bounces => average(&|ball, _, _| ball.bounces as f32),
+            Source::TombolaAngle => tombolas
+                .iter()
+                .find(|(tombola, _)| tombola.index == mapping.tombola)
+                .map(|(_, transform)| {
+                    let angle = transform.rotation.to_euler(EulerRot::ZYX).0;
+                    angle.rem_euclid(TAU).to_degrees()
+                })
+                .unwrap_or_default(),
+            Source::BallCount => count as f32,
+        };
+
+        let event = mapping.to_event(value);
+        if state.sent.get(&index) != Some(&event) {
+            midi.write(event.clone());
+            state.sent.insert(index, event);
+        }
+    }
+}
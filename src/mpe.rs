@@ -0,0 +1,377 @@
+use crate::ball::Ball;
+use crate::midi;
+use crate::midi::MidiOutputEvent;
+use crate::settings::Settings;
+use avian2d::prelude::LinearVelocity;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::RangeInclusive;
+use strum_macros::EnumIter;
+
+pub const MAX_MEMBER_CHANNELS: u8 = 15;
+pub const MAX_PITCH_BEND_RANGE: u8 = 96;
+
+const TIMBRE: u8 = 74;
+const RPN_MSB: u8 = 101;
+const RPN_LSB: u8 = 100;
+const DATA_ENTRY: u8 = 6;
+const PITCH_BEND_SENSITIVITY: u8 = 0;
+const MPE_CONFIGURATION: u8 = 6;
+const PITCH_BEND_CENTRE: u16 = 0x2000;
+const MAX_PITCH_BEND: u16 = 0x3FFF;
+
+pub struct MpePlugin;
+
+impl Plugin for MpePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, send_expression);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, EnumIter, Serialize, Deserialize)]
+pub enum Zone {
+    Lower,
+    Upper,
+}
+
+impl fmt::Display for Zone {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Zone::Lower => write!(f, "Lower Zone"),
+            Zone::Upper => write!(f, "Upper Zone"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct MpeSettings {
+    pub enabled: bool,
+    pub zone: Zone,
+    pub member_channels: u8,
+    pub pitch_bend_range: u8,
+    pub bend_speed: f32,
+    pub pressure_speed: f32,
+    pub timbre_range: (f32, f32),
+}
+
+impl Default for MpeSettings {
+    fn default() -> Self {
+        MpeSettings {
+            enabled: false,
+            zone: Zone::Lower,
+            member_channels: MAX_MEMBER_CHANNELS,
+            pitch_bend_range: 48,
+            bend_speed: 500.0,
+            pressure_speed: 750.0,
+            timbre_range: (-400.0, 400.0),
+        }
+    }
+}
+
+impl MpeSettings {
+    pub fn master_channel(&self) -> u8 {
+        match self.zone {
+            Zone::Lower => midi::MIN_CHANNEL,
+            Zone::Upper => midi::MAX_CHANNEL,
+        }
+    }
+
+    pub fn member_channels(&self) -> RangeInclusive<u8> {
+        let count = self.member_channels.clamp(1, MAX_MEMBER_CHANNELS);
+
+        match self.zone {
+            Zone::Lower => midi::MIN_CHANNEL + 1..=midi::MIN_CHANNEL + count,
+            Zone::Upper => midi::MAX_CHANNEL - count..=midi::MAX_CHANNEL - 1,
+        }
+    }
+
+    pub fn configuration(&self) -> Vec<MidiOutputEvent> {
+        let members = if self.enabled {
+            self.member_channels.clamp(1, MAX_MEMBER_CHANNELS)
+        } else {
+            0
+        };
+
+        let mut events = rpn(self.master_channel(), MPE_CONFIGURATION, members);
+        if self.enabled {
+            for channel in self.member_channels() {
+                events.extend(rpn(
+                    channel,
+                    PITCH_BEND_SENSITIVITY,
+                    self.pitch_bend_range.min(MAX_PITCH_BEND_RANGE),
+                ));
+            }
+        }
+
+        events
+    }
+}
+
+fn rpn(channel: u8, parameter: u8, value: u8) -> Vec<MidiOutputEvent> {
+    [(RPN_MSB, 0), (RPN_LSB, parameter), (DATA_ENTRY, value)]
+        .into_iter()
+        .map(|(controller, value)| MidiOutputEvent::ControlChange {
+            controller,
+            value,
+            channel,
+        })
+        .collect()
+}
+
+#[derive(Default)]
+struct Voice {
+    ball: Option<Entity>,
+    notes: usize,
+    expression: Option<(u16, u8, u8)>,
+}
+
+pub struct Allocation {
+    pub member: u8,
+    pub allocated: bool,
+    pub stolen: Vec<u8>,
+}
+
+#[derive(Resource, Default)]
+pub struct MpeVoices {
+    next: u8,
+    voices: HashMap<u8, Voice>,
+    notes: HashMap<(u8, u8), u8>,
+}
+
+impl MpeVoices {
+    pub fn note_on(
+        &mut self,
+        mpe: &MpeSettings,
+        pitch: u8,
+        channel: u8,
+        ball: Option<Entity>,
+    ) -> Allocation {
+        let channels: Vec<u8> = mpe.member_channels().collect();
+
+        let owned = ball.and_then(|ball| {
+            channels.iter().copied().find(|member| {
+                self.voices
+                    .get(member)
+                    .is_some_and(|voice| voice.notes > 0 && voice.ball == Some(ball))
+            })
+        });
+
+        let member = owned.unwrap_or_else(|| {
+            let start = self.next as usize % channels.len();
+            let member = (0..channels.len())
+                .map(|offset| channels[(start + offset) % channels.len()])
+                .find(|member| self.voices.get(member).is_none_or(|voice| voice.notes == 0))
+                .unwrap_or(channels[start]);
+            let index = channels
+                .iter()
+                .position(|channel| *channel == member)
+                .unwrap_or_default();
+            self.next = ((index + 1) % channels.len()) as u8;
+            member
+        });
+
+        let allocated = owned.is_none();
+        let stolen = if allocated {
+            self.steal(member)
+        } else {
+            Vec::new()
+        };
+
+        if let Some(previous) = self.notes.insert((channel, pitch), member) {
+            self.release(previous);
+        }
+
+        let voice = self.voices.entry(member).or_default();
+        if allocated {
+            voice.ball = ball;
+            voice.expression = None;
+        }
+        voice.notes += 1;
+
+        Allocation {
+            member,
+            allocated,
+            stolen,
+        }
+    }
+
+    fn steal(&mut self, member: u8) -> Vec<u8> {
+        let mut stolen = Vec::new();
+        self.notes.retain(|(_, pitch), owner| {
+            if *owner == member {
+                stolen.push(*pitch);
+            }
+            *owner != member
+        });
+
+        if let Some(voice) = self.voices.get_mut(&member) {
+            voice.notes = 0;
+            voice.ball = None;
+        }
+
+        stolen
+    }
+
+    pub fn note_off(&mut self, pitch: u8, channel: u8) -> Option<u8> {
+        let member = self.notes.remove(&(channel, pitch))?;
+        self.release(member);
+        Some(member)
+    }
+
+    fn release(&mut self, member: u8) {
+        if let Some(voice) = self.voices.get_mut(&member) {
+            voice.notes = voice.notes.saturating_sub(1);
+            if voice.notes == 0 {
+                voice.ball = None;
+            }
+        }
+    }
+}
+
+pub fn initial_expression(member: u8) -> Vec<MidiOutputEvent> {
+    vec![
+        MidiOutputEvent::PitchBend {
+            value: PITCH_BEND_CENTRE,
+            channel: member,
+        },
+        MidiOutputEvent::ChannelPressure {
+            value: 0,
+            channel: member,
+        },
+    ]
+}
+
+fn send_expression(
+    mut voices: ResMut<MpeVoices>,
+    balls: Query<(&Transform, &LinearVelocity), With<Ball>>,
+    settings: Res<Settings>,
+    mut midi: EventWriter<MidiOutputEvent>,
+) {
+    let mpe = &settings.midi.mpe;
+    if !mpe.enabled {
+        return;
+    }
+
+    for (member, voice) in voices.voices.iter_mut() {
+        if voice.notes == 0 {
+            continue;
+        }
+
+        if let Some(Ok((transform, velocity))) = voice.ball.map(|ball| balls.get(ball)) {
+            let bend = (velocity.x / mpe.bend_speed.max(1.0)).clamp(-1.0, 1.0);
+            let bend = (PITCH_BEND_CENTRE as f32 + bend * PITCH_BEND_CENTRE as f32)
+                .clamp(0.0, MAX_PITCH_BEND as f32) as u16;
+
+            let pressure = (velocity.length() / mpe.pressure_speed.max(1.0)).clamp(0.0, 1.0);
+            let pressure = (pressure * midi::MAX_CC_VALUE as f32).round() as u8;
+
+            let (from, to) = mpe.timbre_range;
+            let timbre = if (to - from).abs() < f32::EPSILON {
+                0.0
+            } else {
+                ((transform.translation.y - from) / (to - from)).clamp(0.0, 1.0)
+            };
+            let timbre = (timbre * midi::MAX_CC_VALUE as f32).round() as u8;
+
+            let expression = (bend, pressure, timbre);
+            if voice.expression == Some(expression) {
+                continue;
+            }
+
+            let previous = voice.expression;
+            if previous.is_none_or(|(previous, _, _)| previous != bend) {
+                midi.write(MidiOutputEvent::PitchBend {
+                    value: bend,
+                    channel: *member,
+                });
+            }
+            if previous.is_none_or(|(_, previous, _)| previous != pressure) {
+                midi.write(MidiOutputEvent::ChannelPressure {
+                    value: pressure,
+                    channel: *member,
+                });
+            }
+            if previous.is_none_or(|(_, _, previous)| previous != timbre) {
+                midi.write(MidiOutputEvent::ControlChange {
+                    controller: TIMBRE,
+                    value: timbre,
+                    channel: *member,
+                });
+            }
+            voice.expression = Some(expression);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(member_channels: u8) -> MpeSettings {
+        MpeSettings {
+            enabled: true,
+            member_channels,
+            ..default()
+        }
+    }
+
+    #[test]
+    fn allocates_a_free_member_per_ball() {
+        let mpe = settings(2);
+        let mut voices = MpeVoices::default();
+
+        let first = voices.note_on(&mpe, 60, 1, Some(Entity::from_raw(1)));
+        let second = voices.note_on(&mpe, 64, 1, Some(Entity::from_raw(2)));
+
+        assert_eq!((first.member, second.member), (2, 3));
+        assert!(first.allocated && second.allocated);
+        assert!(first.stolen.is_empty() && second.stolen.is_empty());
+    }
+
+    #[test]
+    fn same_ball_shares_its_member() {
+        let mpe = settings(2);
+        let mut voices = MpeVoices::default();
+        let ball = Some(Entity::from_raw(1));
+
+        let first = voices.note_on(&mpe, 60, 1, ball);
+        let second = voices.note_on(&mpe, 64, 1, ball);
+
+        assert_eq!(first.member, second.member);
+        assert!(!second.allocated);
+        assert!(second.stolen.is_empty());
+    }
+
+    #[test]
+    fn steals_a_busy_member_when_all_are_sounding() {
+        let mpe = settings(2);
+        let mut voices = MpeVoices::default();
+
+        let first = voices.note_on(&mpe, 60, 1, Some(Entity::from_raw(1)));
+        voices.note_on(&mpe, 64, 1, Some(Entity::from_raw(2)));
+        let stolen = voices.note_on(&mpe, 67, 1, Some(Entity::from_raw(3)));
+
+        assert_eq!(stolen.member, first.member);
+        assert!(stolen.allocated);
+        assert_eq!(stolen.stolen, vec![60]);
+        assert_eq!(voices.note_off(60, 1), None);
+        assert_eq!(voices.note_off(67, 1), Some(first.member));
+        assert!(voices.voices[&first.member].ball.is_none());
+    }
+
+    #[test]
+    fn note_off_frees_the_member() {
+        let mpe = settings(1);
+        let mut voices = MpeVoices::default();
+
+        let first = voices.note_on(&mpe, 60, 1, Some(Entity::from_raw(1)));
+        assert_eq!(voices.note_off(60, 1), Some(first.member));
+        let second = voices.note_on(&mpe, 62, 1, Some(Entity::from_raw(2)));
+
+        assert_eq!(second.member, first.member);
+        assert!(second.stolen.is_empty());
+    }
+}
@@ -0,0 +1,276 @@
+use crate::ball::BallBundle;
+use crate::collision::BallCollisionPlugin;
+use crate::emitter::EmitterPlugin;
+use crate::midi::MidiPlugin;
+use crate::modulation::ModulationPlugin;
+use crate::mpe::MpePlugin;
+use crate::recorder::{Recorder, RecorderPlugin};
+use crate::sampler::SamplerPlugin;
+use crate::settings::Settings;
+use crate::synth;
+use crate::synth::{SynthHandle, SynthOutput, SynthPlugin};
+use crate::tombola::TombolaPlugin;
+use crate::transport::TransportPlugin;
+use crate::wav;
+use crate::{clean_up_balls, get_gravity, update_bounciness, update_gravity};
+use avian2d::prelude::*;
+use bevy::app::PluginsState;
+use bevy::prelude::*;
+use bevy::render::settings::WgpuSettings;
+use bevy::render::RenderPlugin;
+use bevy::tasks::tick_global_task_pools_on_main_thread;
+use bevy::time::TimeUpdateStrategy;
+use bevy::window::ExitCondition;
+use bevy::winit::WinitPlugin;
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+const TIMESTEP_HZ: f64 = 60.0;
+
+#[derive(Deserialize)]
+pub struct ScriptedBall {
+    pub time: f32,
+    pub position: (f32, f32),
+    pub velocity: (f32, f32),
+    pub size: usize,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Script {
+    pub balls: Vec<ScriptedBall>,
+}
+
+pub enum ScriptError {
+    Io(std::io::Error),
+    Deserialize(ron::error::SpannedError),
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScriptError::Io(error) => write!(f, "{}", error),
+            ScriptError::Deserialize(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+#[derive(Resource)]
+struct PendingBalls(Vec<ScriptedBall>);
+
+pub fn load_script(path: &Path) -> Result<Script, ScriptError> {
+    let text = fs::read_to_string(path).map_err(ScriptError::Io)?;
+    ron::from_str(&text).map_err(ScriptError::Deserialize)
+}
+
+fn spawn_scripted_balls(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut pending: ResMut<PendingBalls>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+) {
+    while pending
+        .0
+        .last()
+        .is_some_and(|ball| ball.time <= time.elapsed_secs())
+    {
+        if let Some(ball) = pending.0.pop() {
+            commands.spawn(BallBundle::new(
+                Vec2::new(ball.position.0, ball.position.1),
+                Vec2::new(ball.velocity.0, ball.velocity.1),
+                ball.size,
+                &settings.world,
+                &mut meshes,
+                &mut materials,
+            ));
+        }
+    }
+}
+
+pub struct Rendering {
+    pub recorder: Recorder,
+    pub samples: Vec<f32>,
+}
+
+pub fn simulate(mut settings: Settings, script: Script, seconds: f32, audio: bool) -> Rendering {
+    let timestep = Duration::from_secs_f64(1.0 / TIMESTEP_HZ);
+    let gravity = get_gravity(settings.world.gravity);
+    if audio {
+        settings.synth.output = SynthOutput::Internal;
+    }
+
+    let mut balls = script.balls;
+    balls.sort_by(|a, b| b.time.total_cmp(&a.time));
+
+    let mut app = App::new();
+    app.add_plugins((
+        DefaultPlugins
+            .set(WindowPlugin {
+                primary_window: None,
+                exit_condition: ExitCondition::DontExit,
+                ..default()
+            })
+            .set(RenderPlugin {
+                render_creation: WgpuSettings {
+                    backends: None,
+                    ..default()
+                }
+                .into(),
+                ..default()
+            })
+            .disable::<WinitPlugin>(),
+        PhysicsPlugins::default(),
+        MidiPlugin { offline: true },
+        RecorderPlugin,
+        TombolaPlugin,
+        TransportPlugin,
+        EmitterPlugin,
+        BallCollisionPlugin,
+        ModulationPlugin,
+        MpePlugin,
+        SynthPlugin { offline: true },
+        SamplerPlugin { offline: true },
+    ))
+    .add_systems(
+        Update,
+        (
+            spawn_scripted_balls,
+            clean_up_balls,
+            update_gravity,
+            update_bounciness,
+        ),
+    )
+    .insert_resource(TimeUpdateStrategy::ManualDuration(timestep))
+    .insert_resource(Time::<Fixed>::from_duration(timestep))
+    .insert_resource(Gravity(gravity))
+    .insert_resource(PendingBalls(balls))
+    .insert_resource(settings);
+
+    while app.plugins_state() == PluginsState::Adding {
+        tick_global_task_pools_on_main_thread();
+    }
+    app.finish();
+    app.cleanup();
+
+    app.world_mut().resource_mut::<Recorder>().start();
+
+    let synth = app.world().resource::<SynthHandle>().clone();
+    let samples_per_step = (synth::SAMPLE_RATE as f64 / TIMESTEP_HZ).round() as usize;
+    let mut samples = Vec::new();
+
+    let steps = (seconds as f64 * TIMESTEP_HZ).ceil() as usize;
+    for _ in 0..steps {
+        app.update();
+
+        if audio {
+            let start = samples.len();
+            samples.resize(start + samples_per_step, 0.0);
+            if let Ok(mut synth) = synth.0.lock() {
+                synth.render(&mut samples[start..]);
+            }
+        }
+    }
+
+    let mut recorder = app
+        .world_mut()
+        .remove_resource::<Recorder>()
+        .unwrap_or_default();
+    recorder.stop();
+
+    Rendering { recorder, samples }
+}
+
+pub fn render(
+    settings: Settings,
+    script: Script,
+    seconds: f32,
+    output: &Path,
+) -> std::io::Result<()> {
+    let bpm = settings.midi.clock.bpm;
+    let audio = output
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("wav"));
+    let rendering = simulate(settings, script, seconds, audio);
+
+    if audio {
+        println!(
+            "Rendered {:.1} seconds of audio to {}",
+            rendering.samples.len() as f32 / synth::SAMPLE_RATE as f32,
+            output.display()
+        );
+        return wav::write(output, &rendering.samples, synth::SAMPLE_RATE);
+    }
+
+    println!(
+        "Rendered {} notes to {}",
+        rendering.recorder.num_notes(),
+        output.display()
+    );
+
+    rendering.recorder.export(output, bpm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midi;
+    use crate::smf;
+
+    const BPM: f32 = 120.0;
+
+    fn script() -> Script {
+        ron::from_str(
+            "(balls: [
+                (time: 0.0, position: (0.0, 0.0), velocity: (0.0, -300.0), size: 0),
+                (time: 0.5, position: (50.0, 50.0), velocity: (200.0, 0.0), size: 0),
+            ])",
+        )
+        .unwrap()
+    }
+
+    fn note_ons(recorder: &Recorder) -> Vec<u8> {
+        recorder
+            .to_tracks(BPM)
+            .iter()
+            .flat_map(|track| track.events.iter())
+            .filter(|event| event.message[0] & 0xF0 == midi::NOTE_ON_MSG && event.message[2] > 0)
+            .map(|event| event.message[1])
+            .collect()
+    }
+
+    #[test]
+    fn renders_pad_notes() {
+        let settings = Settings::default();
+        let ball = settings.world.ball_size(0);
+        let tombola = &settings.world.tombolas[0];
+        let expected: Vec<u8> = tombola
+            .pads
+            .iter()
+            .enumerate()
+            .flat_map(|(index, pad)| settings.midi.pitches(pad.note, &ball, tombola, index))
+            .collect();
+
+        let rendering = simulate(Settings::default(), script(), 2.0, false);
+        let pitches = note_ons(&rendering.recorder);
+
+        assert!(!pitches.is_empty());
+        assert!(pitches.iter().all(|pitch| expected.contains(pitch)));
+        assert!(rendering.samples.is_empty());
+    }
+
+    #[test]
+    fn renders_deterministically() {
+        let first = simulate(Settings::default(), script(), 2.0, false);
+        let second = simulate(Settings::default(), script(), 2.0, false);
+
+        assert_eq!(
+            smf::encode(&first.recorder.to_tracks(BPM), BPM),
+            smf::encode(&second.recorder.to_tracks(BPM), BPM)
+        );
+    }
+}
@@ -0,0 +1,438 @@
+use crate::ball::{Ball, BallBundle};
+use crate::settings::Settings;
+use crate::tombola::PadHit;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::net::UdpSocket;
+
+const MAX_PACKET_SIZE: usize = 4096;
+const BUNDLE_TAG: &str = "#bundle";
+const SETTINGS_PREFIX: &str = "/settings/";
+const SEND_ADDRESS: &str = "0.0.0.0";
+
+pub struct OscPlugin;
+
+impl Plugin for OscPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(OscServer {
+            socket: None,
+            listening: None,
+            sender: None,
+        });
+        app.add_systems(
+            Update,
+            (
+                update_osc_socket,
+                receive_osc.after(update_osc_socket),
+                send_pad_hits.after(update_osc_socket),
+            ),
+        );
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct OscSettings {
+    pub enabled: bool,
+    pub address: String,
+    pub port: u16,
+    pub output: bool,
+    pub target: String,
+}
+
+impl Default for OscSettings {
+    fn default() -> Self {
+        OscSettings {
+            enabled: false,
+            address: "127.0.0.1".to_string(),
+            port: 9000,
+            output: false,
+            target: "127.0.0.1:9001".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Argument {
+    Int(i32),
+    Float(f32),
+    String(String),
+}
+
+impl Argument {
+    fn to_f32(&self) -> Option<f32> {
+        match self {
+            Argument::Int(value) => Some(*value as f32),
+            Argument::Float(value) => Some(*value),
+            Argument::String(value) => value.parse().ok(),
+        }
+    }
+
+    fn to_json(&self, current: &Value) -> Value {
+        match self {
+            Argument::String(value) => {
+                serde_json::from_str(value).unwrap_or(Value::String(value.clone()))
+            }
+            _ => {
+                let value = self.to_f32().unwrap_or_default();
+                if current.is_boolean() {
+                    Value::Bool(value != 0.0)
+                } else if current.is_u64() || current.is_i64() {
+                    Value::from(value.round() as i64)
+                } else {
+                    Value::from(value as f64)
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Message {
+    pub address: String,
+    pub arguments: Vec<Argument>,
+}
+
+fn write_string(bytes: &mut Vec<u8>, value: &str) {
+    bytes.extend_from_slice(value.as_bytes());
+    bytes.push(0);
+    while !bytes.len().is_multiple_of(4) {
+        bytes.push(0);
+    }
+}
+
+fn read_string(bytes: &[u8], offset: &mut usize) -> Option<String> {
+    let length = bytes.get(*offset..)?.iter().position(|byte| *byte == 0)?;
+    let value = String::from_utf8(bytes[*offset..*offset + length].to_vec()).ok()?;
+    *offset += (length + 4) & !3;
+
+    Some(value)
+}
+
+fn read_word(bytes: &[u8], offset: &mut usize) -> Option<[u8; 4]> {
+    let word = bytes.get(*offset..*offset + 4)?.try_into().ok()?;
+    *offset += 4;
+
+    Some(word)
+}
+
+impl Message {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_string(&mut bytes, &self.address);
+
+        let tags: String = self
+            .arguments
+            .iter()
+            .map(|argument| match argument {
+                Argument::Int(_) => 'i',
+                Argument::Float(_) => 'f',
+                Argument::String(_) => 's',
+            })
+            .collect();
+        write_string(&mut bytes, &format!(",{}", tags));
+
+        for argument in self.arguments.iter() {
+            match argument {
+                Argument::Int(value) => bytes.extend_from_slice(&value.to_be_bytes()),
+                Argument::Float(value) => bytes.extend_from_slice(&value.to_be_bytes()),
+                Argument::String(value) => write_string(&mut bytes, value),
+            }
+        }
+
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Message> {
+        let mut offset = 0;
+        let address = read_string(bytes, &mut offset)?;
+        let tags = if offset < bytes.len() {
+            read_string(bytes, &mut offset)?
+        } else {
+            ",".to_string()
+        };
+
+        let mut arguments = Vec::new();
+        for tag in tags.strip_prefix(',')?.chars() {
+            let argument = match tag {
+                'i' => Argument::Int(i32::from_be_bytes(read_word(bytes, &mut offset)?)),
+                'f' => Argument::Float(f32::from_be_bytes(read_word(bytes, &mut offset)?)),
+                's' => Argument::String(read_string(bytes, &mut offset)?),
+                'T' => Argument::Int(1),
+                'F' => Argument::Int(0),
+                _ => return None,
+            };
+            arguments.push(argument);
+        }
+
+        Some(Message { address, arguments })
+    }
+}
+
+pub fn decode_packet(bytes: &[u8]) -> Vec<Message> {
+    let mut offset = 0;
+    if read_string(bytes, &mut offset).as_deref() != Some(BUNDLE_TAG) {
+        return Message::decode(bytes).into_iter().collect();
+    }
+
+    let mut messages = Vec::new();
+    offset += 8;
+    while let Some(size) = read_word(bytes, &mut offset) {
+        let size = u32::from_be_bytes(size) as usize;
+        if let Some(element) = bytes.get(offset..offset + size) {
+            messages.extend(decode_packet(element));
+        }
+        offset += size;
+    }
+
+    messages
+}
+
+#[derive(Resource)]
+struct OscServer {
+    socket: Option<UdpSocket>,
+    listening: Option<(String, u16)>,
+    sender: Option<UdpSocket>,
+}
+
+fn update_osc_socket(mut server: ResMut<OscServer>, settings: Res<Settings>) {
+    if !settings.osc.output {
+        server.sender = None;
+    } else if server.sender.is_none() {
+        match UdpSocket::bind((SEND_ADDRESS, 0)) {
+            Ok(socket) => server.sender = Some(socket),
+            Err(error) => println!("Failed to open OSC output socket: {}", error),
+        }
+    }
+
+    let listening = if settings.osc.enabled {
+        Some((settings.osc.address.trim().to_string(), settings.osc.port))
+    } else {
+        None
+    };
+
+    if server.listening == listening {
+        return;
+    }
+
+    server.listening = listening.clone();
+    server.socket = None;
+
+    if let Some((address, port)) = listening {
+        match UdpSocket::bind((address.as_str(), port)) {
+            Ok(socket) => {
+                if socket.set_nonblocking(true).is_ok() {
+                    println!("Listening for OSC on {}:{}", address, port);
+                    server.socket = Some(socket);
+                }
+            }
+            Err(error) => println!(
+                "Failed to listen for OSC on {}:{}: {}",
+                address, port, error
+            ),
+        }
+    }
+}
+
+fn set_setting(settings: &mut Settings, path: &str, argument: &Argument) {
+    let mut value = match serde_json::to_value(&*settings) {
+        Ok(value) => value,
+        Err(error) => {
+            println!("Failed to read settings: {}", error);
+            return;
+        }
+    };
+
+    let mut field = &mut value;
+    for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+        let next = match field {
+            Value::Array(items) => segment
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| items.get_mut(index)),
+            Value::Object(fields) => fields.get_mut(segment),
+            _ => None,
+        };
+
+        if let Some(next) = next {
+            field = next;
+        } else {
+            println!("Unknown OSC settings path /settings/{}", path);
+            return;
+        }
+    }
+    *field = argument.to_json(field);
+
+    match serde_json::from_value(value) {
+        Ok(updated) => {
+            *settings = updated;
+            settings.fit_to_shapes();
+        }
+        Err(error) => println!("Invalid value for /settings/{}: {}", path, error),
+    }
+}
+
+fn receive_osc(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    server: Res<OscServer>,
+    mut settings: ResMut<Settings>,
+    balls: Query<Entity, With<Ball>>,
+) {
+    if let Some(socket) = &server.socket {
+        let mut buffer = [0; MAX_PACKET_SIZE];
+        while let Ok(size) = socket.recv(&mut buffer) {
+            for message in decode_packet(&buffer[..size]) {
+                let numbers: Vec<f32> = message
+                    .arguments
+                    .iter()
+                    .filter_map(|argument| argument.to_f32())
+                    .collect();
+
+                match message.address.as_str() {
+                    "/ball/spawn" => {
+                        let value = |index: usize| numbers.get(index).copied().unwrap_or_default();
+                        commands.spawn(BallBundle::new(
+                            Vec2::new(value(0), value(1)),
+                            Vec2::new(value(2), value(3)),
+                            value(4).max(0.0) as usize,
+                            &settings.world,
+                            &mut meshes,
+                            &mut materials,
+                        ));
+                    }
+                    "/ball/clear" => {
+                        for entity in balls.iter() {
+                            commands.entity(entity).despawn();
+                        }
+                    }
+                    address => {
+                        if let (Some(path), Some(argument)) = (
+                            address.strip_prefix(SETTINGS_PREFIX),
+                            message.arguments.first(),
+                        ) {
+                            set_setting(settings.as_mut(), path, argument);
+                        } else {
+                            println!("Unknown OSC message {}", address);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn send_pad_hits(mut hits: EventReader<PadHit>, server: Res<OscServer>, settings: Res<Settings>) {
+    if let Some(socket) = &server.sender {
+        for hit in hits.read() {
+            if !settings.osc.output {
+                continue;
+            }
+
+            let message = Message {
+                address: "/pad/hit".to_string(),
+                arguments: vec![
+                    Argument::Int(hit.tombola as i32),
+                    Argument::Int(hit.pad as i32),
+                    Argument::Int(hit.note as i32),
+                    Argument::Int(hit.velocity as i32),
+                    Argument::Int(hit.size as i32),
+                    Argument::Float(hit.position.x),
+                    Argument::Float(hit.position.y),
+                ],
+            };
+            let _ = socket.send_to(&message.encode(), settings.osc.target.as_str());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundle(elements: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_string(&mut bytes, BUNDLE_TAG);
+        bytes.extend_from_slice(&1u64.to_be_bytes());
+        for element in elements {
+            bytes.extend_from_slice(&(element.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(element);
+        }
+        bytes
+    }
+
+    fn message(address: &str, arguments: Vec<Argument>) -> Message {
+        Message {
+            address: address.to_string(),
+            arguments,
+        }
+    }
+
+    #[test]
+    fn encode_message() {
+        #[rustfmt::skip]
+        let expected = [
+            b'/', b'a', 0, 0,
+            b',', b'i', b's', 0,
+            0, 0, 0, 7,
+            b'h', b'i', 0, 0,
+        ];
+
+        let encoded = message(
+            "/a",
+            vec![Argument::Int(7), Argument::String("hi".to_string())],
+        )
+        .encode();
+        assert_eq!(encoded, expected);
+    }
+
+    #[test]
+    fn message_round_trip() {
+        let original = message(
+            "/pad/hit",
+            vec![
+                Argument::Int(-3),
+                Argument::Float(0.25),
+                Argument::String("four".to_string()),
+            ],
+        );
+
+        assert_eq!(decode_packet(&original.encode()), vec![original]);
+    }
+
+    #[test]
+    fn decode_booleans() {
+        let mut bytes = Vec::new();
+        write_string(&mut bytes, "/settings/world/ball_collisions");
+        write_string(&mut bytes, ",TF");
+
+        assert_eq!(
+            decode_packet(&bytes),
+            vec![message(
+                "/settings/world/ball_collisions",
+                vec![Argument::Int(1), Argument::Int(0)],
+            )]
+        );
+    }
+
+    #[test]
+    fn decode_bundles() {
+        let spawn = message("/ball/spawn", vec![Argument::Float(1.0)]);
+        let clear = message("/ball/clear", Vec::new());
+        let gravity = message("/settings/world/gravity", vec![Argument::Float(0.5)]);
+
+        let nested = bundle(&[gravity.encode()]);
+        let packet = bundle(&[spawn.encode(), nested, clear.encode()]);
+
+        assert_eq!(decode_packet(&packet), vec![spawn, gravity, clear]);
+    }
+
+    #[test]
+    fn decode_truncated_message() {
+        let encoded = message("/a", vec![Argument::Int(7)]).encode();
+
+        assert!(decode_packet(&encoded[..encoded.len() - 2]).is_empty());
+    }
+}
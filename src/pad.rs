@@ -3,11 +3,17 @@ use avian2d::prelude::*;
 use bevy::prelude::*;
 use std::collections::HashMap;
 
+pub struct PlayingNote {
+    pub channel: u8,
+    pub timer: Timer,
+}
+
 #[derive(Component)]
 pub struct Pad {
+    pub tombola: usize,
     pub index: usize,
     pub note: midi::Note,
-    pub playing_notes: HashMap<i32, Timer>,
+    pub playing_notes: HashMap<u8, PlayingNote>,
     pub material: MeshMaterial2d<ColorMaterial>,
 }
 
@@ -32,7 +38,9 @@ pub struct PadBundle {
 }
 
 impl PadBundle {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
+        tombola: usize,
         index: usize,
         size: Vec2,
         transform: Transform,
@@ -45,6 +53,7 @@ impl PadBundle {
 
         PadBundle {
             marker: Pad {
+                tombola,
                 index,
                 note,
                 material: material.clone(),
@@ -58,3 +67,41 @@ impl PadBundle {
         }
     }
 }
+
+#[derive(Component)]
+pub struct Wall;
+
+impl Wall {
+    pub fn color() -> Color {
+        Color::linear_rgb(0.1, 0.1, 0.1)
+    }
+}
+
+#[derive(Bundle)]
+pub struct WallBundle {
+    marker: Wall,
+    transform: Transform,
+    restitution: Restitution,
+    collider: Collider,
+    mesh: Mesh2d,
+    material: MeshMaterial2d<ColorMaterial>,
+}
+
+impl WallBundle {
+    pub fn new(
+        size: Vec2,
+        transform: Transform,
+        bounciness: f32,
+        meshes: &mut ResMut<Assets<Mesh>>,
+        materials: &mut ResMut<Assets<ColorMaterial>>,
+    ) -> Self {
+        WallBundle {
+            marker: Wall,
+            transform,
+            restitution: Restitution::new(bounciness),
+            collider: Collider::rectangle(size.x, size.y),
+            mesh: Mesh2d(meshes.add(Rectangle::new(size.x, size.y))),
+            material: MeshMaterial2d(materials.add(Wall::color())),
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use crate::settings::Settings;
+use bevy::prelude::Resource;
+use ron::ser::PrettyConfig;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const PRESET_DIR: &str = "presets";
+const PRESET_EXTENSION: &str = "ron";
+
+pub enum PresetError {
+    Io(std::io::Error),
+    Serialize(ron::Error),
+    Deserialize(ron::error::SpannedError),
+}
+
+impl fmt::Display for PresetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PresetError::Io(error) => write!(f, "{}", error),
+            PresetError::Serialize(error) => write!(f, "{}", error),
+            PresetError::Deserialize(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Presets {
+    pub current: Option<PathBuf>,
+    pub selected: String,
+    pub new_name: String,
+}
+
+impl Presets {
+    pub fn current_name(&self) -> Option<String> {
+        self.current.as_ref().map(|path| name_of(path))
+    }
+}
+
+const DEFAULT_NAME: &str = "untitled";
+
+pub fn file_name(name: &str, extension: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let name = name.trim().trim_start_matches('.');
+    let name = if name.is_empty() { DEFAULT_NAME } else { name };
+
+    format!("{}.{}", name, extension)
+}
+
+pub fn path_for(name: &str) -> PathBuf {
+    Path::new(PRESET_DIR).join(file_name(name, PRESET_EXTENSION))
+}
+
+pub fn name_of(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+pub fn list() -> Vec<String> {
+    let mut names = Vec::new();
+
+    if let Ok(entries) = fs::read_dir(PRESET_DIR) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == PRESET_EXTENSION) {
+                names.push(name_of(&path));
+            }
+        }
+    }
+
+    names.sort();
+    names
+}
+
+pub fn save(settings: &Settings, path: &Path) -> Result<(), PresetError> {
+    let text = ron::ser::to_string_pretty(settings, PrettyConfig::default())
+        .map_err(PresetError::Serialize)?;
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(PresetError::Io)?;
+    }
+
+    fs::write(path, text).map_err(PresetError::Io)
+}
+
+pub fn load(path: &Path) -> Result<Settings, PresetError> {
+    let text = fs::read_to_string(path).map_err(PresetError::Io)?;
+    let mut settings: Settings = ron::from_str(&text).map_err(PresetError::Deserialize)?;
+    settings.fit_to_shapes();
+
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_for_keeps_dotted_names() {
+        assert_eq!(path_for("live.v2"), Path::new("presets/live.v2.ron"));
+        assert_eq!(name_of(&path_for("live.v2")), "live.v2");
+    }
+
+    #[test]
+    fn path_for_stays_in_preset_dir() {
+        assert_eq!(path_for("../settings"), Path::new("presets/_settings.ron"));
+        assert_eq!(
+            path_for("/etc/passwd"),
+            Path::new("presets/_etc_passwd.ron")
+        );
+        assert_eq!(path_for(".."), Path::new("presets/untitled.ron"));
+        assert_eq!(path_for("  "), Path::new("presets/untitled.ron"));
+    }
+}
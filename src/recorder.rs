@@ -0,0 +1,146 @@
+use crate::midi;
+use crate::preset;
+use crate::smf;
+use bevy::prelude::*;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const RECORDING_DIR: &str = "recordings";
+const RECORDING_EXTENSION: &str = "mid";
+
+pub struct RecorderPlugin;
+
+impl Plugin for RecorderPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Recorder {
+            file_name: "recording".to_string(),
+            ..default()
+        });
+        app.add_systems(Update, advance_recorder);
+    }
+}
+
+struct RecordedEvent {
+    time: Duration,
+    message: [u8; 3],
+}
+
+#[derive(Resource, Default)]
+pub struct Recorder {
+    recording: bool,
+    elapsed: Duration,
+    events: Vec<RecordedEvent>,
+    held_notes: HashSet<(u8, u8)>,
+    pub file_name: String,
+}
+
+impl Recorder {
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    pub fn has_recording(&self) -> bool {
+        !self.recording && !self.events.is_empty()
+    }
+
+    pub fn num_notes(&self) -> usize {
+        self.events
+            .iter()
+            .filter(|event| is_note_on(&event.message))
+            .count()
+    }
+
+    pub fn start(&mut self) {
+        self.events.clear();
+        self.held_notes.clear();
+        self.elapsed = Duration::ZERO;
+        self.recording = true;
+    }
+
+    pub fn stop(&mut self) {
+        if !self.recording {
+            return;
+        }
+
+        let mut held_notes: Vec<(u8, u8)> = self.held_notes.drain().collect();
+        held_notes.sort();
+        for (status, note) in held_notes {
+            let channel = (status & 0x0F) + midi::MIN_CHANNEL;
+            self.events.push(RecordedEvent {
+                time: self.elapsed,
+                message: [midi::status(midi::NOTE_OFF_MSG, channel), note, 0x7F],
+            });
+        }
+
+        self.recording = false;
+    }
+
+    pub fn record(&mut self, message: &[u8]) {
+        if !self.recording {
+            return;
+        }
+
+        let message: [u8; 3] = match message.try_into() {
+            Ok(message) => message,
+            Err(_) => return,
+        };
+
+        let key = (message[0] & 0x0F, message[1]);
+        if is_note_on(&message) {
+            self.held_notes.insert(key);
+        } else if is_note_off(&message) {
+            self.held_notes.remove(&key);
+        }
+
+        self.events.push(RecordedEvent {
+            time: self.elapsed,
+            message,
+        });
+    }
+
+    pub fn to_tracks(&self, bpm: f32) -> Vec<smf::Track> {
+        let mut channels: BTreeMap<u8, Vec<smf::TrackEvent>> = BTreeMap::new();
+
+        for event in self.events.iter() {
+            channels
+                .entry(event.message[0] & 0x0F)
+                .or_default()
+                .push(smf::TrackEvent {
+                    tick: smf::to_ticks(event.time, bpm),
+                    message: event.message,
+                });
+        }
+
+        channels
+            .into_iter()
+            .map(|(channel, events)| smf::Track {
+                name: format!("Channel {}", channel + midi::MIN_CHANNEL),
+                events,
+            })
+            .collect()
+    }
+
+    pub fn export(&self, path: &Path, bpm: f32) -> std::io::Result<()> {
+        smf::write(path, &self.to_tracks(bpm), bpm)
+    }
+}
+
+fn is_note_on(message: &[u8; 3]) -> bool {
+    message[0] & 0xF0 == midi::NOTE_ON_MSG && message[2] > 0
+}
+
+fn is_note_off(message: &[u8; 3]) -> bool {
+    message[0] & 0xF0 == midi::NOTE_OFF_MSG
+        || (message[0] & 0xF0 == midi::NOTE_ON_MSG && message[2] == 0)
+}
+
+pub fn path_for(name: &str) -> PathBuf {
+    Path::new(RECORDING_DIR).join(preset::file_name(name, RECORDING_EXTENSION))
+}
+
+fn advance_recorder(mut recorder: ResMut<Recorder>, time: Res<Time>) {
+    if recorder.recording {
+        recorder.elapsed += time.delta();
+    }
+}
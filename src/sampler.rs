@@ -0,0 +1,378 @@
+use crate::midi;
+use crate::settings::Settings;
+use crate::size;
+use crate::size::BallSize;
+use crate::synth::SynthHandle;
+use bevy::math::ops::powf;
+use bevy::prelude::*;
+use bevy::tasks::futures_lite::future;
+use bevy::tasks::{block_on, AsyncComputeTaskPool, Task};
+use rodio::decoder::DecoderError;
+use rodio::{Decoder, Source};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use strum_macros::EnumIter;
+
+pub const MAX_CHOKE_GROUP: usize = 8;
+pub const MAX_SAMPLE_VOICES: usize = 32;
+
+const CHOKE_TIME: f32 = 0.005;
+const RETRY_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Default)]
+pub struct SamplerPlugin {
+    pub offline: bool,
+}
+
+impl Plugin for SamplerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PlaySample>();
+        app.insert_resource(SampleBank {
+            blocking: self.offline,
+            ..default()
+        });
+        app.add_systems(Update, (load_samples, play_samples.after(load_samples)));
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, EnumIter, Serialize, Deserialize)]
+pub enum SampleMode {
+    Replace,
+    Layer,
+}
+
+impl fmt::Display for SampleMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SampleMode::Replace => write!(f, "Instead of MIDI"),
+            SampleMode::Layer => write!(f, "With MIDI"),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PadSample {
+    pub enabled: bool,
+    pub path: String,
+    pub mode: SampleMode,
+    pub gain: f32,
+    pub pitch_by_size: bool,
+    pub choke: usize,
+}
+
+impl Default for PadSample {
+    fn default() -> Self {
+        PadSample {
+            enabled: false,
+            path: String::new(),
+            mode: SampleMode::Replace,
+            gain: 1.0,
+            pitch_by_size: true,
+            choke: 0,
+        }
+    }
+}
+
+impl PadSample {
+    pub fn is_active(&self) -> bool {
+        self.enabled && !self.path.is_empty()
+    }
+
+    pub fn sends_midi(&self) -> bool {
+        !self.is_active() || self.mode == SampleMode::Layer
+    }
+
+    pub fn rate(&self, ball: &BallSize) -> f32 {
+        if !self.pitch_by_size {
+            return 1.0;
+        }
+
+        let notes_per_octave = midi::NOTES_PER_OCTAVE as i32;
+        let semitones = (ball.octave - size::DEFAULT_OCTAVE) * notes_per_octave + ball.transpose;
+        powf(2.0, semitones as f32 / notes_per_octave as f32)
+    }
+
+    pub fn trigger(&self, ball: &BallSize, velocity: u8) -> Option<PlaySample> {
+        if !self.is_active() {
+            return None;
+        }
+
+        Some(PlaySample {
+            path: self.path.clone(),
+            rate: self.rate(ball),
+            gain: self.gain * velocity as f32 / midi::MAX_VELOCITY as f32,
+            choke: self.choke,
+        })
+    }
+}
+
+pub struct Sample {
+    pub data: Vec<f32>,
+    pub sample_rate: u32,
+}
+
+#[derive(Debug)]
+pub enum SampleError {
+    Io(io::Error),
+    Decode(DecoderError),
+    Empty,
+}
+
+impl fmt::Display for SampleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SampleError::Io(error) => write!(f, "{}", error),
+            SampleError::Decode(error) => {
+                write!(f, "{} (only WAV and FLAC files are supported)", error)
+            }
+            SampleError::Empty => write!(f, "the file contains no audio"),
+        }
+    }
+}
+
+pub fn decode(bytes: Vec<u8>) -> Result<Sample, SampleError> {
+    let decoder = Decoder::new(Cursor::new(bytes)).map_err(SampleError::Decode)?;
+    let channels = decoder.channels().max(1) as usize;
+    let sample_rate = decoder.sample_rate();
+    let samples: Vec<i16> = decoder.collect();
+
+    let data: Vec<f32> = samples
+        .chunks(channels)
+        .map(|frame| {
+            let sum: f32 = frame.iter().map(|sample| *sample as f32 / 32768.0).sum();
+            sum / frame.len() as f32
+        })
+        .collect();
+    if data.is_empty() {
+        return Err(SampleError::Empty);
+    }
+
+    Ok(Sample { data, sample_rate })
+}
+
+pub fn load(path: &Path) -> Result<Sample, SampleError> {
+    decode(fs::read(path).map_err(SampleError::Io)?)
+}
+
+pub struct SampleVoice {
+    sample: Arc<Sample>,
+    position: f64,
+    step: f64,
+    gain: f32,
+    choke: usize,
+    fade: f32,
+    fade_step: f32,
+}
+
+impl SampleVoice {
+    pub fn new(sample: Arc<Sample>, rate: f32, gain: f32, choke: usize, sample_rate: f32) -> Self {
+        let step = rate as f64 * sample.sample_rate as f64 / sample_rate as f64;
+
+        SampleVoice {
+            sample,
+            position: 0.0,
+            step,
+            gain,
+            choke,
+            fade: 1.0,
+            fade_step: 0.0,
+        }
+    }
+
+    pub fn chokes(&self, other: &SampleVoice) -> bool {
+        self.choke > 0 && self.choke == other.choke
+    }
+
+    pub fn choke(&mut self, sample_rate: f32) {
+        self.fade_step = 1.0 / (CHOKE_TIME * sample_rate);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.fade <= 0.0 || self.position as usize + 1 >= self.sample.data.len()
+    }
+
+    pub fn next_sample(&mut self) -> f32 {
+        if self.is_finished() {
+            return 0.0;
+        }
+
+        let data = &self.sample.data;
+        let index = self.position as usize;
+        let fraction = self.position.fract() as f32;
+        let value = data[index] + (data[index + 1] - data[index]) * fraction;
+
+        self.position += self.step;
+        self.fade = (self.fade - self.fade_step).max(0.0);
+
+        value * self.gain * self.fade
+    }
+}
+
+#[derive(Event, Clone)]
+pub struct PlaySample {
+    pub path: String,
+    pub rate: f32,
+    pub gain: f32,
+    pub choke: usize,
+}
+
+#[derive(Resource, Default)]
+pub struct SampleBank {
+    blocking: bool,
+    samples: HashMap<String, Arc<Sample>>,
+    loading: HashMap<String, Task<Result<Sample, SampleError>>>,
+    failed: HashMap<String, (Duration, String)>,
+}
+
+impl SampleBank {
+    fn store(&mut self, path: String, sample: Result<Sample, SampleError>, now: Duration) {
+        match sample {
+            Ok(sample) => {
+                println!(
+                    "Loaded sample {} ({:.2} seconds)",
+                    path,
+                    sample.data.len() as f32 / sample.sample_rate.max(1) as f32
+                );
+                self.failed.remove(&path);
+                self.samples.insert(path, Arc::new(sample));
+            }
+            Err(error) => {
+                let message = error.to_string();
+                let reported = self
+                    .failed
+                    .get(&path)
+                    .is_some_and(|(_, previous)| *previous == message);
+                if !reported {
+                    println!("Could not load sample {}: {}", path, message);
+                }
+                self.failed.insert(path, (now, message));
+            }
+        }
+    }
+}
+
+fn load_samples(settings: Res<Settings>, time: Res<Time<Real>>, mut bank: ResMut<SampleBank>) {
+    let now = time.elapsed();
+    let paths: HashSet<&String> = settings
+        .world
+        .tombolas
+        .iter()
+        .flat_map(|tombola| tombola.pads.iter())
+        .map(|pad| &pad.sample)
+        .filter(|sample| sample.is_active())
+        .map(|sample| &sample.path)
+        .collect();
+
+    bank.samples.retain(|path, _| paths.contains(path));
+    bank.failed.retain(|path, _| paths.contains(path));
+
+    for path in paths {
+        let waiting = bank
+            .failed
+            .get(path)
+            .is_some_and(|(failed, _)| now < *failed + RETRY_INTERVAL);
+        if waiting || bank.samples.contains_key(path) || bank.loading.contains_key(path) {
+            continue;
+        }
+
+        if bank.blocking {
+            let sample = load(Path::new(path));
+            bank.store(path.clone(), sample, now);
+        } else {
+            let file = path.clone();
+            let task = AsyncComputeTaskPool::get().spawn(async move { load(Path::new(&file)) });
+            bank.loading.insert(path.clone(), task);
+        }
+    }
+
+    let mut finished = Vec::new();
+    bank.loading
+        .retain(|path, task| match block_on(future::poll_once(task)) {
+            Some(sample) => {
+                finished.push((path.clone(), sample));
+                false
+            }
+            None => true,
+        });
+    for (path, sample) in finished {
+        bank.store(path, sample, now);
+    }
+}
+
+fn play_samples(
+    mut events: EventReader<PlaySample>,
+    bank: Res<SampleBank>,
+    synth: Res<SynthHandle>,
+) {
+    if let Ok(mut synth) = synth.0.lock() {
+        for event in events.read() {
+            if let Some(sample) = bank.samples.get(&event.path) {
+                synth.play_sample(sample.clone(), event.rate, event.gain, event.choke);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wav;
+
+    #[test]
+    fn decodes_wav_bytes() {
+        let samples: Vec<f32> = (0..64).map(|index| (index as f32 / 32.0) - 1.0).collect();
+        let sample = decode(wav::encode(&samples, 22050)).unwrap();
+
+        assert_eq!(sample.sample_rate, 22050);
+        assert_eq!(sample.data.len(), samples.len());
+        for (decoded, original) in sample.data.iter().zip(samples.iter()) {
+            assert!((decoded - original).abs() < 0.001);
+        }
+    }
+
+    #[test]
+    fn rate_follows_octave_and_transpose() {
+        let pad_sample = PadSample::default();
+        let mut ball = BallSize::default();
+        assert_eq!(pad_sample.rate(&ball), 1.0);
+
+        ball.octave = size::DEFAULT_OCTAVE + 1;
+        assert_eq!(pad_sample.rate(&ball), 2.0);
+
+        ball.octave = size::DEFAULT_OCTAVE;
+        ball.transpose = -12;
+        assert_eq!(pad_sample.rate(&ball), 0.5);
+    }
+
+    #[test]
+    fn rejects_unsupported_bytes() {
+        assert!(matches!(
+            decode(b"not an audio file".to_vec()),
+            Err(SampleError::Decode(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_wav_without_audio() {
+        assert!(matches!(
+            decode(wav::encode(&[], 44100)),
+            Err(SampleError::Empty)
+        ));
+    }
+
+    #[test]
+    fn reports_missing_files() {
+        let error = load(Path::new("samples/does-not-exist.wav")).err().unwrap();
+
+        assert!(matches!(error, SampleError::Io(_)));
+        assert!(!error.to_string().contains("WAV and FLAC"));
+    }
+}
@@ -0,0 +1,230 @@
+use crate::midi::{Note, NOTES_PER_OCTAVE};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use strum_macros::EnumIter;
+
+pub type IntervalMask = [bool; NOTES_PER_OCTAVE];
+
+#[derive(Clone, Copy, PartialEq, EnumIter, Serialize, Deserialize)]
+pub enum Scale {
+    Chromatic,
+    Major,
+    Dorian,
+    Phrygian,
+    Lydian,
+    Mixolydian,
+    NaturalMinor,
+    Locrian,
+    HarmonicMinor,
+    MelodicMinor,
+    MajorPentatonic,
+    MinorPentatonic,
+    WholeTone,
+    Custom,
+}
+
+impl Scale {
+    fn intervals(&self) -> &'static [usize] {
+        match self {
+            Scale::Chromatic | Scale::Custom => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+            Scale::Major => &[0, 2, 4, 5, 7, 9, 11],
+            Scale::Dorian => &[0, 2, 3, 5, 7, 9, 10],
+            Scale::Phrygian => &[0, 1, 3, 5, 7, 8, 10],
+            Scale::Lydian => &[0, 2, 4, 6, 7, 9, 11],
+            Scale::Mixolydian => &[0, 2, 4, 5, 7, 9, 10],
+            Scale::NaturalMinor => &[0, 2, 3, 5, 7, 8, 10],
+            Scale::Locrian => &[0, 1, 3, 5, 6, 8, 10],
+            Scale::HarmonicMinor => &[0, 2, 3, 5, 7, 8, 11],
+            Scale::MelodicMinor => &[0, 2, 3, 5, 7, 9, 11],
+            Scale::MajorPentatonic => &[0, 2, 4, 7, 9],
+            Scale::MinorPentatonic => &[0, 3, 5, 7, 10],
+            Scale::WholeTone => &[0, 2, 4, 6, 8, 10],
+        }
+    }
+
+    pub fn to_mask(self, custom: &IntervalMask) -> IntervalMask {
+        if self == Scale::Custom {
+            let mut mask = *custom;
+            mask[0] = true;
+            return mask;
+        }
+
+        let mut mask = [false; NOTES_PER_OCTAVE];
+        for interval in self.intervals() {
+            mask[*interval] = true;
+        }
+
+        mask
+    }
+}
+
+impl fmt::Display for Scale {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Scale::Chromatic => write!(f, "Chromatic"),
+            Scale::Major => write!(f, "Major"),
+            Scale::Dorian => write!(f, "Dorian"),
+            Scale::Phrygian => write!(f, "Phrygian"),
+            Scale::Lydian => write!(f, "Lydian"),
+            Scale::Mixolydian => write!(f, "Mixolydian"),
+            Scale::NaturalMinor => write!(f, "Natural Minor"),
+            Scale::Locrian => write!(f, "Locrian"),
+            Scale::HarmonicMinor => write!(f, "Harmonic Minor"),
+            Scale::MelodicMinor => write!(f, "Melodic Minor"),
+            Scale::MajorPentatonic => write!(f, "Major Pentatonic"),
+            Scale::MinorPentatonic => write!(f, "Minor Pentatonic"),
+            Scale::WholeTone => write!(f, "Whole Tone"),
+            Scale::Custom => write!(f, "Custom"),
+        }
+    }
+}
+
+pub fn contains(key: Note, mask: &IntervalMask, note: Note) -> bool {
+    let interval = (note.to_index() + NOTES_PER_OCTAVE - key.to_index()) % NOTES_PER_OCTAVE;
+    mask[interval]
+}
+
+pub fn notes(key: Note, mask: &IntervalMask) -> Vec<Note> {
+    (0..NOTES_PER_OCTAVE)
+        .filter(|interval| mask[*interval])
+        .map(|interval| key.transpose(interval as i32))
+        .collect()
+}
+
+pub fn snap(key: Note, mask: &IntervalMask, note: Note) -> Note {
+    for distance in 0..NOTES_PER_OCTAVE as i32 {
+        for candidate in [note.transpose(-distance), note.transpose(distance)] {
+            if contains(key, mask, candidate) {
+                return candidate;
+            }
+        }
+    }
+
+    note
+}
+
+pub fn change_key<'a>(
+    notes: impl IntoIterator<Item = &'a mut Note>,
+    from: Note,
+    to: Note,
+    mask: &IntervalMask,
+) {
+    let semitones = to.to_index() as i32 - from.to_index() as i32;
+    let from_scale = self::notes(from, mask);
+    let to_scale = self::notes(to, mask);
+
+    for note in notes {
+        *note = match from_scale.iter().position(|degree| degree == note) {
+            Some(degree) => to_scale[degree],
+            None => note.transpose(semitones),
+        };
+    }
+}
+
+pub fn fill<'a>(notes: impl IntoIterator<Item = &'a mut Note>, key: Note, mask: &IntervalMask) {
+    let scale = self::notes(key, mask);
+    if scale.is_empty() {
+        return;
+    }
+
+    for (index, note) in notes.into_iter().enumerate() {
+        *note = scale[index % scale.len()];
+    }
+}
+
+pub fn randomize<'a>(
+    notes: impl IntoIterator<Item = &'a mut Note>,
+    key: Note,
+    mask: &IntervalMask,
+) {
+    let scale = self::notes(key, mask);
+    let mut rng = rand::thread_rng();
+
+    for note in notes {
+        if let Some(choice) = scale.choose(&mut rng) {
+            *note = *choice;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mask(intervals: &[usize]) -> IntervalMask {
+        let mut mask = [false; NOTES_PER_OCTAVE];
+        for interval in intervals {
+            mask[*interval] = true;
+        }
+        mask
+    }
+
+    #[test]
+    fn custom_scale_always_contains_the_root() {
+        let custom = mask(&[4, 7]);
+
+        assert_eq!(Scale::Custom.to_mask(&custom), mask(&[0, 4, 7]));
+        assert_eq!(
+            Scale::MajorPentatonic.to_mask(&custom),
+            mask(&[0, 2, 4, 7, 9])
+        );
+    }
+
+    #[test]
+    fn snap_prefers_the_note_below() {
+        let major = Scale::Major.to_mask(&[false; NOTES_PER_OCTAVE]);
+
+        assert_eq!(snap(Note::C, &major, Note::CSharp), Note::C);
+        assert_eq!(snap(Note::C, &major, Note::FSharp), Note::F);
+        assert_eq!(snap(Note::C, &major, Note::E), Note::E);
+    }
+
+    #[test]
+    fn snap_wraps_around_the_octave() {
+        let fifths = mask(&[0, 7]);
+
+        assert_eq!(snap(Note::C, &fifths, Note::B), Note::C);
+        assert_eq!(snap(Note::C, &fifths, Note::CSharp), Note::C);
+        assert_eq!(snap(Note::A, &mask(&[0, 3]), Note::GSharp), Note::A);
+        assert_eq!(snap(Note::A, &mask(&[0, 3]), Note::D), Note::C);
+    }
+
+    #[test]
+    fn change_key_moves_scale_degrees() {
+        let minor_triad = mask(&[0, 3, 7]);
+        let mut notes = [Note::C, Note::DSharp, Note::G, Note::E];
+
+        change_key(&mut notes, Note::C, Note::D, &minor_triad);
+
+        assert_eq!(notes, [Note::D, Note::F, Note::A, Note::FSharp]);
+    }
+
+    #[test]
+    fn change_key_wraps_around_the_octave() {
+        let minor_triad = mask(&[0, 3, 7]);
+        let mut notes = [Note::A, Note::C, Note::E];
+
+        change_key(&mut notes, Note::A, Note::D, &minor_triad);
+
+        assert_eq!(notes, [Note::D, Note::F, Note::A]);
+    }
+
+    #[test]
+    fn fill_cycles_through_the_scale() {
+        let mut notes = [Note::C; 5];
+
+        fill(&mut notes, Note::G, &mask(&[0, 4, 7]));
+
+        assert_eq!(notes, [Note::G, Note::B, Note::D, Note::G, Note::B]);
+    }
+
+    #[test]
+    fn fill_ignores_an_empty_scale() {
+        let mut notes = [Note::E, Note::F];
+
+        fill(&mut notes, Note::C, &[false; NOTES_PER_OCTAVE]);
+
+        assert_eq!(notes, [Note::E, Note::F]);
+    }
+}
@@ -1,83 +1,333 @@
-use crate::geometry::Shape;
+use crate::collision::BallCollisionNotes;
+use crate::emitter::EmitterSettings;
+use crate::keyboard::KeyboardInput;
+use crate::learn::MidiBinding;
 use crate::midi;
+use crate::modulation::Modulation;
+use crate::mpe::MpeSettings;
+use crate::osc::OscSettings;
+use crate::scale::{IntervalMask, Scale};
+use crate::size;
+use crate::size::BallSize;
+use crate::synth::SynthSettings;
+use crate::tombola::TombolaSettings;
+use crate::transport::{ClockSource, Quantize};
+use crate::velocity::VelocityCurve;
 use bevy::prelude::Resource;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct NumBallsLimit {
     pub enabled: bool,
     pub limit: usize,
 }
 
+impl Default for NumBallsLimit {
+    fn default() -> Self {
+        NumBallsLimit {
+            enabled: false,
+            limit: 10,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct BounceLimit {
     pub enabled: bool,
     pub limit: usize,
 }
 
+impl Default for BounceLimit {
+    fn default() -> Self {
+        BounceLimit {
+            enabled: false,
+            limit: 5,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct World {
-    pub tombola_shape: Shape,
-    pub tombola_spin: f32,
+    pub tombolas: Vec<TombolaSettings>,
+    pub palette: Vec<BallSize>,
     pub bounciness: f32,
     pub gravity: f32,
     pub max_balls: NumBallsLimit,
     pub max_bounces: BounceLimit,
+    pub ball_collisions: bool,
+    pub emitters: Vec<EmitterSettings>,
 }
 
+impl Default for World {
+    fn default() -> Self {
+        World {
+            tombolas: vec![TombolaSettings::default()],
+            palette: size::default_palette(),
+            bounciness: 1.0,
+            gravity: 1.0,
+            max_balls: NumBallsLimit::default(),
+            max_bounces: BounceLimit::default(),
+            ball_collisions: true,
+            emitters: Vec::new(),
+        }
+    }
+}
+
+impl World {
+    pub fn ball_size(&self, index: usize) -> BallSize {
+        self.palette.get(index).cloned().unwrap_or_default()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct FixedNoteVelocity {
     pub enabled: bool,
     pub value: u8,
 }
 
+impl Default for FixedNoteVelocity {
+    fn default() -> Self {
+        FixedNoteVelocity {
+            enabled: false,
+            value: 64,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct FixedNoteLength {
     pub enabled: bool,
     pub value: u64,
 }
 
+impl Default for FixedNoteLength {
+    fn default() -> Self {
+        FixedNoteLength {
+            enabled: false,
+            value: 100,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct MidiClock {
+    pub enabled: bool,
+    pub bpm: f32,
+    pub source: ClockSource,
+}
+
+impl Default for MidiClock {
+    fn default() -> Self {
+        MidiClock {
+            enabled: false,
+            bpm: 120.0,
+            source: ClockSource::Internal,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChannelOverride {
+    pub enabled: bool,
+    pub value: u8,
+}
+
+impl Default for ChannelOverride {
+    fn default() -> Self {
+        ChannelOverride {
+            enabled: false,
+            value: midi::MIN_CHANNEL,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FixedPitch {
+    pub enabled: bool,
+    pub value: u8,
+}
+
+impl Default for FixedPitch {
+    fn default() -> Self {
+        FixedPitch {
+            enabled: false,
+            value: 60,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Pitch {
+    pub transpose: i32,
+    pub out_of_range: midi::OutOfRange,
+}
+
+impl Pitch {
+    pub fn root(&self, note: midi::Note, ball: &BallSize, fixed: Option<&FixedPitch>) -> i32 {
+        let value = match fixed {
+            Some(fixed) if fixed.enabled => fixed.value as i32,
+            _ => note.to_value(ball.octave),
+        };
+
+        value + self.transpose + ball.transpose
+    }
+}
+
+impl Default for Pitch {
+    fn default() -> Self {
+        Pitch {
+            transpose: 0,
+            out_of_range: midi::OutOfRange::Clamp,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Key {
+    pub root: midi::Note,
+    pub scale: Scale,
+    pub custom_intervals: IntervalMask,
+}
+
+impl Key {
+    pub fn to_mask(&self) -> IntervalMask {
+        self.scale.to_mask(&self.custom_intervals)
+    }
+}
+
+impl Default for Key {
+    fn default() -> Self {
+        Key {
+            root: midi::Note::C,
+            scale: Scale::Chromatic,
+            custom_intervals: [true; midi::NOTES_PER_OCTAVE],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct Midi {
-    pub tombola_notes: Vec<midi::Note>,
+    pub key: Key,
+    pub pitch: Pitch,
+    pub velocity: VelocityCurve,
     pub fixed_note_velocity: FixedNoteVelocity,
     pub fixed_note_length: FixedNoteLength,
+    pub collision_notes: BallCollisionNotes,
+    pub modulation: Modulation,
+    pub mpe: MpeSettings,
+    pub keyboard: KeyboardInput,
+    pub bindings: Vec<MidiBinding>,
+    pub clock: MidiClock,
+    pub quantize: Quantize,
 }
 
-#[derive(Resource)]
+impl Default for Midi {
+    fn default() -> Self {
+        Midi {
+            key: Key::default(),
+            pitch: Pitch::default(),
+            velocity: VelocityCurve::default(),
+            fixed_note_velocity: FixedNoteVelocity::default(),
+            fixed_note_length: FixedNoteLength::default(),
+            collision_notes: BallCollisionNotes::default(),
+            modulation: Modulation::default(),
+            mpe: MpeSettings::default(),
+            keyboard: KeyboardInput::default(),
+            bindings: Vec::new(),
+            clock: MidiClock::default(),
+            quantize: Quantize::Off,
+        }
+    }
+}
+
+impl Midi {
+    pub fn pitches(
+        &self,
+        note: midi::Note,
+        ball: &BallSize,
+        tombola: &TombolaSettings,
+        pad_index: usize,
+    ) -> Vec<u8> {
+        let pad = tombola.pads.get(pad_index);
+        let root = self.pitch.root(note, ball, pad.map(|pad| &pad.fixed_pitch));
+        let stack = match pad {
+            Some(pad) => pad.chord.stack(root),
+            None => vec![root],
+        };
+
+        let mut pitches: Vec<u8> = stack
+            .into_iter()
+            .map(|value| self.pitch.out_of_range.apply(value))
+            .collect();
+        pitches.sort();
+        pitches.dedup();
+
+        pitches
+    }
+}
+
+#[derive(Resource, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Settings {
     pub world: World,
     pub midi: Midi,
+    pub osc: OscSettings,
+    pub synth: SynthSettings,
 }
 
-impl Default for Settings {
-    fn default() -> Self {
-        Settings {
-            world: World {
-                tombola_shape: Shape::Hexagon,
-                tombola_spin: 1.5,
-                bounciness: 1.0,
-                gravity: 1.0,
-                max_balls: NumBallsLimit {
-                    enabled: false,
-                    limit: 10,
-                },
-                max_bounces: BounceLimit {
-                    enabled: false,
-                    limit: 5,
-                },
-            },
-            midi: Midi {
-                tombola_notes: vec![
-                    midi::Note::C,
-                    midi::Note::E,
-                    midi::Note::G,
-                    midi::Note::ASharp,
-                    midi::Note::D,
-                    midi::Note::F,
-                ],
-                fixed_note_velocity: FixedNoteVelocity {
-                    enabled: false,
-                    value: 64,
-                },
-                fixed_note_length: FixedNoteLength {
-                    enabled: false,
-                    value: 100,
-                },
-            },
+impl Settings {
+    pub fn fit_to_shapes(&mut self) {
+        for tombola in self.world.tombolas.iter_mut() {
+            tombola.fit_to_shape();
+        }
+    }
+
+    pub fn remove_tombola(&mut self, index: usize) {
+        self.world.tombolas.remove(index);
+
+        self.midi
+            .bindings
+            .retain_mut(|binding| match binding.parameter.without_tombola(index) {
+                Some(parameter) => {
+                    binding.parameter = parameter;
+                    true
+                }
+                None => false,
+            });
+
+        for mapping in self.midi.modulation.mappings.iter_mut() {
+            mapping.tombola = index_after_removal(mapping.tombola, index).unwrap_or_default();
+        }
+
+        self.midi.keyboard.tombola =
+            index_after_removal(self.midi.keyboard.tombola, index).unwrap_or_default();
+    }
+
+    pub fn remove_ball_size(&mut self, index: usize) {
+        self.world.palette.remove(index);
+
+        for emitter in self.world.emitters.iter_mut() {
+            emitter.size = index_after_removal(emitter.size, index).unwrap_or_default();
         }
     }
 }
+
+pub fn index_after_removal(index: usize, removed: usize) -> Option<usize> {
+    match index.cmp(&removed) {
+        Ordering::Less => Some(index),
+        Ordering::Equal => None,
+        Ordering::Greater => Some(index - 1),
+    }
+}
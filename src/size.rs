@@ -1,50 +1,93 @@
+use crate::midi;
+use crate::synth::Patch;
 use bevy::color::Color;
+use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone, PartialEq)]
-pub enum Size {
-    Small,
-    Medium,
-    Large,
+pub const MIN_RADIUS: f32 = 3.0;
+pub const MAX_RADIUS: f32 = 40.0;
+pub const DEFAULT_OCTAVE: i32 = 3;
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RestitutionOverride {
+    pub enabled: bool,
+    pub value: f32,
 }
 
-impl Size {
-    pub const fn to_octave(&self) -> i32 {
-        match self {
-            Size::Small => 4,
-            Size::Medium => 3,
-            Size::Large => 2,
+impl Default for RestitutionOverride {
+    fn default() -> Self {
+        RestitutionOverride {
+            enabled: false,
+            value: 1.0,
         }
     }
+}
 
-    pub const fn to_radius(&self) -> f32 {
-        match self {
-            Size::Small => 10.0,
-            Size::Medium => 15.0,
-            Size::Large => 25.0,
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BallSize {
+    pub name: String,
+    pub radius: f32,
+    pub density: f32,
+    pub color: [f32; 3],
+    pub brightness: f32,
+    pub restitution: RestitutionOverride,
+    pub octave: i32,
+    pub transpose: i32,
+    pub channel: u8,
+    pub patch: Patch,
+}
+
+impl Default for BallSize {
+    fn default() -> Self {
+        BallSize {
+            name: "Ball".to_string(),
+            radius: 15.0,
+            density: 1.0,
+            color: [1.0, 1.0, 1.0],
+            brightness: 1.5,
+            restitution: RestitutionOverride::default(),
+            octave: DEFAULT_OCTAVE,
+            transpose: 0,
+            channel: midi::MIN_CHANNEL,
+            patch: Patch::default(),
         }
     }
+}
 
-    pub const fn to_color(&self) -> Color {
-        match self {
-            Size::Small => Color::linear_rgb(1.8, 0.3, 0.3),
-            Size::Medium => Color::linear_rgb(1.5, 1.3, 0.3),
-            Size::Large => Color::linear_rgb(0.2, 0.2, 2.3),
+impl BallSize {
+    fn new(name: &str, radius: f32, color: [f32; 3], brightness: f32, octave: i32) -> Self {
+        BallSize {
+            name: name.to_string(),
+            radius,
+            color,
+            brightness,
+            octave,
+            ..Default::default()
         }
     }
 
-    pub fn increment(&self) -> Size {
-        match self {
-            Size::Small => Size::Medium,
-            Size::Medium => Size::Large,
-            Size::Large => Size::Large,
-        }
+    pub fn to_color(&self) -> Color {
+        Color::linear_rgb(
+            self.color[0] * self.brightness,
+            self.color[1] * self.brightness,
+            self.color[2] * self.brightness,
+        )
     }
 
-    pub fn decrement(&self) -> Size {
-        match self {
-            Size::Small => Size::Small,
-            Size::Medium => Size::Small,
-            Size::Large => Size::Medium,
+    pub fn restitution(&self, bounciness: f32) -> f32 {
+        if self.restitution.enabled {
+            return self.restitution.value;
         }
+
+        bounciness
     }
 }
+
+pub fn default_palette() -> Vec<BallSize> {
+    vec![
+        BallSize::new("Small", 10.0, [1.0, 0.17, 0.17], 1.8, 4),
+        BallSize::new("Medium", 15.0, [1.0, 0.87, 0.2], 1.5, 3),
+        BallSize::new("Large", 25.0, [0.09, 0.09, 1.0], 2.3, 2),
+    ]
+}
@@ -0,0 +1,166 @@
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+pub const TICKS_PER_QUARTER: u16 = 480;
+
+const END_OF_TRACK: [u8; 3] = [0xFF, 0x2F, 0x00];
+const TRACK_NAME: [u8; 2] = [0xFF, 0x03];
+const SET_TEMPO: [u8; 3] = [0xFF, 0x51, 0x03];
+
+pub struct TrackEvent {
+    pub tick: u32,
+    pub message: [u8; 3],
+}
+
+pub struct Track {
+    pub name: String,
+    pub events: Vec<TrackEvent>,
+}
+
+pub fn to_ticks(time: Duration, bpm: f32) -> u32 {
+    let quarters = time.as_secs_f64() * bpm as f64 / 60.0;
+    (quarters * TICKS_PER_QUARTER as f64).round() as u32
+}
+
+fn write_variable_length(bytes: &mut Vec<u8>, value: u32) {
+    let mut groups = vec![(value & 0x7F) as u8];
+    let mut value = value >> 7;
+
+    while value > 0 {
+        groups.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+
+    bytes.extend(groups.iter().rev());
+}
+
+fn write_chunk(bytes: &mut Vec<u8>, id: &[u8; 4], data: &[u8]) {
+    bytes.extend_from_slice(id);
+    bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(data);
+}
+
+fn encode_tempo_track(bpm: f32) -> Vec<u8> {
+    let micros_per_quarter = (60_000_000.0 / bpm).round() as u32;
+
+    let mut data = Vec::new();
+    write_variable_length(&mut data, 0);
+    data.extend_from_slice(&SET_TEMPO);
+    data.extend_from_slice(&micros_per_quarter.to_be_bytes()[1..]);
+    write_variable_length(&mut data, 0);
+    data.extend_from_slice(&END_OF_TRACK);
+
+    data
+}
+
+fn encode_track(track: &Track) -> Vec<u8> {
+    let mut data = Vec::new();
+
+    write_variable_length(&mut data, 0);
+    data.extend_from_slice(&TRACK_NAME);
+    write_variable_length(&mut data, track.name.len() as u32);
+    data.extend_from_slice(track.name.as_bytes());
+
+    let mut events: Vec<&TrackEvent> = track.events.iter().collect();
+    events.sort_by_key(|event| event.tick);
+
+    let mut last_tick = 0;
+    for event in events {
+        write_variable_length(&mut data, event.tick - last_tick);
+        data.extend_from_slice(&event.message);
+        last_tick = event.tick;
+    }
+
+    write_variable_length(&mut data, 0);
+    data.extend_from_slice(&END_OF_TRACK);
+
+    data
+}
+
+pub fn encode(tracks: &[Track], bpm: f32) -> Vec<u8> {
+    let mut bytes = Vec::new();
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&1u16.to_be_bytes());
+    header.extend_from_slice(&(tracks.len() as u16 + 1).to_be_bytes());
+    header.extend_from_slice(&TICKS_PER_QUARTER.to_be_bytes());
+    write_chunk(&mut bytes, b"MThd", &header);
+
+    write_chunk(&mut bytes, b"MTrk", &encode_tempo_track(bpm));
+    for track in tracks {
+        write_chunk(&mut bytes, b"MTrk", &encode_track(track));
+    }
+
+    bytes
+}
+
+pub fn write(path: &Path, tracks: &[Track], bpm: f32) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(path, encode(tracks, bpm))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variable_length(value: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_variable_length(&mut bytes, value);
+        bytes
+    }
+
+    #[test]
+    fn variable_length_quantities() {
+        assert_eq!(variable_length(0), [0x00]);
+        assert_eq!(variable_length(0x7F), [0x7F]);
+        assert_eq!(variable_length(0x80), [0x81, 0x00]);
+        assert_eq!(variable_length(0x3FFF), [0xFF, 0x7F]);
+        assert_eq!(variable_length(0x4000), [0x81, 0x80, 0x00]);
+        assert_eq!(variable_length(0x0FFF_FFFF), [0xFF, 0xFF, 0xFF, 0x7F]);
+    }
+
+    #[test]
+    fn tempo_track() {
+        assert_eq!(
+            encode_tempo_track(120.0),
+            [0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20, 0x00, 0xFF, 0x2F, 0x00]
+        );
+    }
+
+    #[test]
+    fn encode_file() {
+        let track = Track {
+            name: "A".to_string(),
+            events: vec![
+                TrackEvent {
+                    tick: 480,
+                    message: [0x80, 0x3C, 0x00],
+                },
+                TrackEvent {
+                    tick: 0,
+                    message: [0x90, 0x3C, 0x64],
+                },
+            ],
+        };
+
+        #[rustfmt::skip]
+        let expected = [
+            b'M', b'T', b'h', b'd', 0x00, 0x00, 0x00, 0x06,
+            0x00, 0x01, 0x00, 0x02, 0x01, 0xE0,
+            b'M', b'T', b'r', b'k', 0x00, 0x00, 0x00, 0x0B,
+            0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20,
+            0x00, 0xFF, 0x2F, 0x00,
+            b'M', b'T', b'r', b'k', 0x00, 0x00, 0x00, 0x12,
+            0x00, 0xFF, 0x03, 0x01, b'A',
+            0x00, 0x90, 0x3C, 0x64,
+            0x83, 0x60, 0x80, 0x3C, 0x00,
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+
+        assert_eq!(encode(&[track], 120.0), expected);
+    }
+}
@@ -0,0 +1,556 @@
+use crate::ball::Ball;
+use crate::midi;
+use crate::midi::MidiOutputEvent;
+use crate::sampler;
+use crate::sampler::{Sample, SampleVoice};
+use crate::settings::Settings;
+use avian2d::math::{PI, TAU};
+use bevy::audio::{AddAudioSource, Decodable, Source};
+use bevy::math::ops::{powf, sin, tanh};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use strum_macros::EnumIter;
+
+pub const SAMPLE_RATE: u32 = 44100;
+pub const MAX_POLYPHONY: usize = 64;
+
+const BLOCK_SIZE: usize = 256;
+const A4_PITCH: u8 = 69;
+const A4_FREQUENCY: f32 = 440.0;
+
+#[derive(Default)]
+pub struct SynthPlugin {
+    pub offline: bool,
+}
+
+impl Plugin for SynthPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SynthHandle(Arc::new(Mutex::new(Synth::new(SAMPLE_RATE)))));
+        app.add_systems(Update, play_synth);
+
+        if self.offline {
+            return;
+        }
+
+        app.add_audio_source::<SynthAudio>();
+        app.add_systems(Startup, start_synth_audio);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, EnumIter, Serialize, Deserialize)]
+pub enum SynthOutput {
+    External,
+    Internal,
+    Both,
+}
+
+impl SynthOutput {
+    pub fn sends_midi(&self) -> bool {
+        *self != SynthOutput::Internal
+    }
+
+    pub fn plays_synth(&self) -> bool {
+        *self != SynthOutput::External
+    }
+}
+
+impl fmt::Display for SynthOutput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SynthOutput::External => write!(f, "External MIDI"),
+            SynthOutput::Internal => write!(f, "Internal Synth"),
+            SynthOutput::Both => write!(f, "Both"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct SynthSettings {
+    pub output: SynthOutput,
+    pub volume: f32,
+    pub polyphony: usize,
+}
+
+impl Default for SynthSettings {
+    fn default() -> Self {
+        SynthSettings {
+            output: SynthOutput::External,
+            volume: 0.5,
+            polyphony: 16,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, EnumIter, Serialize, Deserialize)]
+pub enum Waveform {
+    Sine,
+    Saw,
+    Square,
+    Triangle,
+}
+
+impl Waveform {
+    fn sample(&self, phase: f32) -> f32 {
+        let phase = phase.rem_euclid(1.0);
+
+        match self {
+            Waveform::Sine => sin(TAU * phase),
+            Waveform::Saw => 2.0 * phase - 1.0,
+            Waveform::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Triangle => 4.0 * (phase - 0.5).abs() - 1.0,
+        }
+    }
+}
+
+impl fmt::Display for Waveform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Waveform::Sine => write!(f, "Sine"),
+            Waveform::Saw => write!(f, "Saw"),
+            Waveform::Square => write!(f, "Square"),
+            Waveform::Triangle => write!(f, "Triangle"),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Patch {
+    pub waveform: Waveform,
+    pub fm_ratio: f32,
+    pub fm_depth: f32,
+    pub cutoff: f32,
+    pub resonance: f32,
+    pub attack: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+    pub gain: f32,
+}
+
+impl Default for Patch {
+    fn default() -> Self {
+        Patch {
+            waveform: Waveform::Saw,
+            fm_ratio: 2.0,
+            fm_depth: 0.0,
+            cutoff: 2000.0,
+            resonance: 0.3,
+            attack: 0.005,
+            decay: 0.2,
+            sustain: 0.6,
+            release: 0.3,
+            gain: 0.8,
+        }
+    }
+}
+
+#[derive(PartialEq)]
+enum Stage {
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+    Done,
+}
+
+fn frequency(pitch: u8) -> f32 {
+    let semitones = pitch as f32 - A4_PITCH as f32;
+    A4_FREQUENCY * powf(2.0, semitones / midi::NOTES_PER_OCTAVE as f32)
+}
+
+struct Voice {
+    pitch: u8,
+    channel: u8,
+    patch: Patch,
+    amplitude: f32,
+    frequency: f32,
+    phase: f32,
+    modulator_phase: f32,
+    stage: Stage,
+    level: f32,
+    release_step: f32,
+    low: f32,
+    band: f32,
+    age: u64,
+}
+
+impl Voice {
+    fn release(&mut self, sample_rate: f32) {
+        self.stage = Stage::Release;
+        self.release_step = self.level / (self.patch.release.max(0.001) * sample_rate);
+    }
+
+    fn next_sample(&mut self, sample_rate: f32) -> f32 {
+        let patch = &self.patch;
+
+        match self.stage {
+            Stage::Attack => {
+                self.level += 1.0 / (patch.attack.max(0.001) * sample_rate);
+                if self.level >= 1.0 {
+                    self.level = 1.0;
+                    self.stage = Stage::Decay;
+                }
+            }
+            Stage::Decay => {
+                self.level -= (1.0 - patch.sustain) / (patch.decay.max(0.001) * sample_rate);
+                if self.level <= patch.sustain {
+                    self.level = patch.sustain;
+                    self.stage = Stage::Sustain;
+                }
+            }
+            Stage::Sustain => {}
+            Stage::Release => {
+                self.level -= self.release_step;
+                if self.level <= 0.0 {
+                    self.level = 0.0;
+                    self.stage = Stage::Done;
+                }
+            }
+            Stage::Done => return 0.0,
+        }
+
+        let modulation = sin(TAU * self.modulator_phase) * patch.fm_depth;
+        let oscillator = patch.waveform.sample(self.phase + modulation);
+
+        self.phase = (self.phase + self.frequency / sample_rate).fract();
+        self.modulator_phase =
+            (self.modulator_phase + self.frequency * patch.fm_ratio / sample_rate).fract();
+
+        let cutoff = patch.cutoff.clamp(20.0, sample_rate / 6.0);
+        let tuning = 2.0 * sin(PI * cutoff / sample_rate);
+        let damping = 2.0 - 2.0 * patch.resonance.clamp(0.0, 0.95);
+        self.low += tuning * self.band;
+        let high = oscillator - self.low - damping * self.band;
+        self.band += tuning * high;
+
+        self.low * self.level * self.amplitude
+    }
+}
+
+pub struct Synth {
+    sample_rate: f32,
+    voices: Vec<Voice>,
+    samples: Vec<SampleVoice>,
+    volume: f32,
+    polyphony: usize,
+    notes_played: u64,
+}
+
+impl Synth {
+    pub fn new(sample_rate: u32) -> Self {
+        Synth {
+            sample_rate: sample_rate as f32,
+            voices: Vec::new(),
+            samples: Vec::new(),
+            volume: SynthSettings::default().volume,
+            polyphony: SynthSettings::default().polyphony,
+            notes_played: 0,
+        }
+    }
+
+    pub fn note_on(&mut self, pitch: u8, velocity: u8, channel: u8, patch: Patch) {
+        if velocity == 0 {
+            self.note_off(pitch, channel);
+            return;
+        }
+
+        self.voices.retain(|voice| voice.stage != Stage::Done);
+        while self.voices.len() >= self.polyphony.max(1) {
+            let stolen = self
+                .voices
+                .iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| {
+                    (a.stage != Stage::Release)
+                        .cmp(&(b.stage != Stage::Release))
+                        .then(a.age.cmp(&b.age))
+                })
+                .map(|(index, _)| index)
+                .unwrap_or_default();
+            self.voices.remove(stolen);
+        }
+
+        self.notes_played += 1;
+        self.voices.push(Voice {
+            pitch,
+            channel,
+            patch,
+            amplitude: velocity as f32 / midi::MAX_VELOCITY as f32,
+            frequency: frequency(pitch),
+            phase: 0.0,
+            modulator_phase: 0.0,
+            stage: Stage::Attack,
+            level: 0.0,
+            release_step: 0.0,
+            low: 0.0,
+            band: 0.0,
+            age: self.notes_played,
+        });
+    }
+
+    pub fn note_off(&mut self, pitch: u8, channel: u8) {
+        let sample_rate = self.sample_rate;
+        for voice in self.voices.iter_mut() {
+            if voice.pitch == pitch && voice.channel == channel && voice.stage != Stage::Release {
+                voice.release(sample_rate);
+            }
+        }
+    }
+
+    pub fn all_notes_off(&mut self) {
+        let sample_rate = self.sample_rate;
+        for voice in self.voices.iter_mut() {
+            voice.release(sample_rate);
+        }
+        for voice in self.samples.iter_mut() {
+            voice.choke(sample_rate);
+        }
+    }
+
+    pub fn play_sample(&mut self, sample: Arc<Sample>, rate: f32, gain: f32, choke: usize) {
+        let sample_rate = self.sample_rate;
+        let voice = SampleVoice::new(sample, rate, gain, choke, sample_rate);
+
+        for other in self.samples.iter_mut() {
+            if voice.chokes(other) {
+                other.choke(sample_rate);
+            }
+        }
+
+        self.samples.retain(|voice| !voice.is_finished());
+        if self.samples.len() >= sampler::MAX_SAMPLE_VOICES {
+            self.samples.remove(0);
+        }
+        self.samples.push(voice);
+    }
+
+    pub fn render(&mut self, samples: &mut [f32]) {
+        let sample_rate = self.sample_rate;
+        for sample in samples.iter_mut() {
+            let mix: f32 = self
+                .voices
+                .iter_mut()
+                .map(|voice| voice.patch.gain * voice.next_sample(sample_rate))
+                .sum::<f32>()
+                + self
+                    .samples
+                    .iter_mut()
+                    .map(|voice| voice.next_sample())
+                    .sum::<f32>();
+            *sample = tanh(mix * self.volume);
+        }
+
+        self.voices.retain(|voice| voice.stage != Stage::Done);
+        self.samples.retain(|voice| !voice.is_finished());
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct SynthHandle(pub Arc<Mutex<Synth>>);
+
+#[derive(Asset, TypePath)]
+struct SynthAudio {
+    synth: Arc<Mutex<Synth>>,
+}
+
+struct SynthDecoder {
+    synth: Arc<Mutex<Synth>>,
+    buffer: Vec<f32>,
+    position: usize,
+}
+
+impl Iterator for SynthDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.position >= self.buffer.len() {
+            self.buffer.resize(BLOCK_SIZE, 0.0);
+            if let Ok(mut synth) = self.synth.lock() {
+                synth.render(&mut self.buffer);
+            } else {
+                self.buffer.fill(0.0);
+            }
+            self.position = 0;
+        }
+
+        let sample = self.buffer[self.position];
+        self.position += 1;
+        Some(sample)
+    }
+}
+
+impl Source for SynthDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl Decodable for SynthAudio {
+    type DecoderItem = f32;
+    type Decoder = SynthDecoder;
+
+    fn decoder(&self) -> Self::Decoder {
+        SynthDecoder {
+            synth: self.synth.clone(),
+            buffer: Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+fn start_synth_audio(
+    mut commands: Commands,
+    mut sources: ResMut<Assets<SynthAudio>>,
+    synth: Res<SynthHandle>,
+) {
+    let source = sources.add(SynthAudio {
+        synth: synth.0.clone(),
+    });
+    commands.spawn(AudioPlayer(source));
+}
+
+fn play_synth(
+    mut events: EventReader<MidiOutputEvent>,
+    synth: Res<SynthHandle>,
+    balls: Query<&Ball>,
+    settings: Res<Settings>,
+) {
+    if let Ok(mut synth) = synth.0.lock() {
+        synth.volume = settings.synth.volume;
+        synth.polyphony = settings.synth.polyphony.clamp(1, MAX_POLYPHONY);
+
+        for event in events.read() {
+            match event {
+                MidiOutputEvent::NoteOn {
+                    pitch,
+                    velocity,
+                    channel,
+                    ball,
+                } if settings.synth.output.plays_synth() => {
+                    let size = ball
+                        .and_then(|ball| balls.get(ball).ok())
+                        .map(|ball| ball.size)
+                        .unwrap_or_default();
+                    let patch = settings.world.ball_size(size).patch;
+                    synth.note_on(*pitch, *velocity, *channel, patch);
+                }
+                MidiOutputEvent::NoteOff { pitch, channel } => synth.note_off(*pitch, *channel),
+                MidiOutputEvent::Stop => synth.all_notes_off(),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patch() -> Patch {
+        Patch {
+            waveform: Waveform::Sine,
+            attack: 0.01,
+            decay: 0.01,
+            sustain: 0.5,
+            release: 0.01,
+            ..default()
+        }
+    }
+
+    fn render(synth: &mut Synth, seconds: f32) -> Vec<f32> {
+        let mut buffer = vec![0.0; (seconds * synth.sample_rate) as usize];
+        synth.render(&mut buffer);
+        buffer
+    }
+
+    fn pitches(synth: &Synth) -> Vec<u8> {
+        synth.voices.iter().map(|voice| voice.pitch).collect()
+    }
+
+    #[test]
+    fn frequencies_are_tuned_to_a4() {
+        assert_eq!(frequency(A4_PITCH), A4_FREQUENCY);
+        assert!((frequency(A4_PITCH + 12) - 2.0 * A4_FREQUENCY).abs() < 0.01);
+        assert!((frequency(60) - 261.63).abs() < 0.01);
+    }
+
+    #[test]
+    fn envelope_reaches_sustain_then_silence() {
+        let mut synth = Synth::new(SAMPLE_RATE);
+        synth.note_on(69, 127, 1, patch());
+
+        let attack = render(&mut synth, 0.05);
+        assert!(attack.iter().any(|sample| sample.abs() > 0.01));
+        assert!(synth.voices[0].stage == Stage::Sustain);
+        assert_eq!(synth.voices[0].level, 0.5);
+
+        synth.note_off(69, 1);
+        render(&mut synth, 0.05);
+        assert!(synth.voices.is_empty());
+        assert!(render(&mut synth, 0.01).iter().all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn voice_stealing_respects_polyphony() {
+        let mut synth = Synth::new(SAMPLE_RATE);
+        synth.polyphony = 2;
+
+        synth.note_on(60, 100, 1, patch());
+        synth.note_on(62, 100, 1, patch());
+        synth.note_on(64, 100, 1, patch());
+        assert_eq!(pitches(&synth), [62, 64]);
+
+        synth.note_on(66, 100, 1, patch());
+        assert_eq!(pitches(&synth), [64, 66]);
+    }
+
+    #[test]
+    fn voice_stealing_prefers_releasing_voices() {
+        let mut synth = Synth::new(SAMPLE_RATE);
+        synth.polyphony = 2;
+
+        synth.note_on(60, 100, 1, patch());
+        synth.note_on(62, 100, 1, patch());
+        synth.note_off(62, 1);
+        synth.note_on(64, 100, 1, patch());
+
+        assert_eq!(pitches(&synth), [60, 64]);
+    }
+
+    #[test]
+    fn zero_velocity_releases_note() {
+        let mut synth = Synth::new(SAMPLE_RATE);
+        synth.note_on(60, 100, 1, patch());
+        render(&mut synth, 0.05);
+
+        synth.note_on(60, 0, 1, patch());
+        assert_eq!(synth.voices.len(), 1);
+        assert!(synth.voices[0].stage == Stage::Release);
+
+        render(&mut synth, 0.05);
+        assert!(synth.voices.is_empty());
+    }
+}
@@ -1,192 +1,722 @@
 use crate::ball::Ball;
+use crate::chord::PadChord;
 use crate::geometry;
+use crate::geometry::Shape;
 use crate::midi;
 use crate::midi::{MidiOutputEvent, Note};
-use crate::pad::{Pad, PadBundle};
-use crate::settings::Settings;
-use avian2d::math::PI;
+use crate::pad::{Pad, PadBundle, PlayingNote, WallBundle};
+use crate::sampler::{PadSample, PlaySample};
+use crate::settings::{ChannelOverride, FixedPitch, Settings};
+use crate::size::BallSize;
+use crate::transport::{advance_internal_clock, Transport};
+use crate::velocity::impact_speed;
+use avian2d::math::{PI, TAU};
 use avian2d::prelude::{
     AngularVelocity, CollisionEventsEnabled, LinearVelocity, OnCollisionStart, RigidBody,
 };
-use bevy::math::ops::tan;
+use bevy::math::ops::sin;
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::time::Duration;
+use strum_macros::EnumIter;
 
 pub struct TombolaPlugin;
 
 impl Plugin for TombolaPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, spawn_default_tombola);
+        app.add_event::<PadHit>();
+        app.init_resource::<QuantizedNotes>();
+        app.init_resource::<ShapeEditor>();
+        app.add_systems(Startup, spawn_tombolas);
         app.add_systems(
             Update,
             (
-                update_tombola_shape,
-                update_tombola_notes.after(update_tombola_shape),
-                update_tombola_spin,
+                update_tombolas,
+                update_tombola_notes.after(update_tombolas),
+                update_tombola_motion.after(update_tombolas),
+                draw_shape_editor,
                 fade_pads,
                 note_off_pads,
+                release_quantized_notes.after(advance_internal_clock),
             ),
         );
     }
 }
 
+#[derive(Clone, Copy, PartialEq, EnumIter, Serialize, Deserialize)]
+pub enum Side {
+    Pad,
+    Wall,
+    Open,
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Side::Pad => write!(f, "Pad"),
+            Side::Wall => write!(f, "Wall"),
+            Side::Open => write!(f, "Open"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, EnumIter, Serialize, Deserialize)]
+pub enum SpinMode {
+    Constant,
+    Sine,
+    Triangle,
+    Pendulum,
+    Stepped,
+}
+
+impl fmt::Display for SpinMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpinMode::Constant => write!(f, "Constant"),
+            SpinMode::Sine => write!(f, "Sine LFO"),
+            SpinMode::Triangle => write!(f, "Triangle LFO"),
+            SpinMode::Pendulum => write!(f, "Pendulum"),
+            SpinMode::Stepped => write!(f, "Stepped"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpinModulation {
+    pub mode: SpinMode,
+    pub rate: f32,
+    pub depth: f32,
+    pub from_angle: f32,
+    pub to_angle: f32,
+}
+
+impl Default for SpinModulation {
+    fn default() -> Self {
+        SpinModulation {
+            mode: SpinMode::Constant,
+            rate: 0.25,
+            depth: 1.0,
+            from_angle: -45.0,
+            to_angle: 45.0,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PadSettings {
+    pub side: Side,
+    pub note: Note,
+    pub fixed_pitch: FixedPitch,
+    pub chord: PadChord,
+    pub channel: ChannelOverride,
+    pub sample: PadSample,
+}
+
+impl Default for PadSettings {
+    fn default() -> Self {
+        PadSettings {
+            side: Side::Pad,
+            note: Note::C,
+            fixed_pitch: FixedPitch::default(),
+            chord: PadChord::default(),
+            channel: ChannelOverride::default(),
+            sample: PadSample::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct TombolaSettings {
+    pub position: (f32, f32),
+    pub radius: f32,
+    pub shape: Shape,
+    pub num_sides: usize,
+    pub star_points: usize,
+    pub star_depth: f32,
+    pub vertex_radii: Vec<f32>,
+    pub vertices: Vec<(f32, f32)>,
+    pub spin: f32,
+    pub modulation: SpinModulation,
+    pub channel: ChannelOverride,
+    pub pads: Vec<PadSettings>,
+}
+
+impl Default for TombolaSettings {
+    fn default() -> Self {
+        TombolaSettings {
+            position: (0.0, 0.0),
+            radius: 225.0,
+            shape: Shape::Hexagon,
+            num_sides: 6,
+            star_points: 5,
+            star_depth: 0.5,
+            vertex_radii: vec![1.0; 6],
+            vertices: Vec::new(),
+            spin: 1.5,
+            modulation: SpinModulation::default(),
+            channel: ChannelOverride::default(),
+            pads: [Note::C, Note::E, Note::G, Note::ASharp, Note::D, Note::F]
+                .into_iter()
+                .map(|note| PadSettings { note, ..default() })
+                .collect(),
+        }
+    }
+}
+
+impl TombolaSettings {
+    pub fn position(&self) -> Vec2 {
+        Vec2::new(self.position.0, self.position.1)
+    }
+
+    pub fn vertices(&self) -> Vec<Vec2> {
+        match self.shape {
+            Shape::Star => geometry::star(self.radius, self.star_depth, self.star_points),
+            Shape::Irregular => geometry::irregular(self.radius, &self.vertex_radii),
+            Shape::Freeform => self
+                .vertices
+                .iter()
+                .map(|vertex| Vec2::new(vertex.0, vertex.1))
+                .collect(),
+            shape => {
+                geometry::polygon(self.radius, shape.get_num_sides().unwrap_or(self.num_sides))
+            }
+        }
+    }
+
+    pub fn angular_velocity(&self, angle: f32, elapsed: f32) -> f32 {
+        const RESPONSE: f32 = 10.0;
+
+        let modulation = &self.modulation;
+        let phase = elapsed * modulation.rate;
+
+        match modulation.mode {
+            SpinMode::Constant => -self.spin,
+            SpinMode::Sine => -(self.spin + modulation.depth * sin(TAU * phase)),
+            SpinMode::Triangle => {
+                let triangle = 4.0 * (phase.fract() - 0.5).abs() - 1.0;
+                -(self.spin + modulation.depth * triangle)
+            }
+            SpinMode::Pendulum => {
+                let from = modulation.from_angle.to_radians();
+                let to = modulation.to_angle.to_radians();
+                let target = (from + to) / 2.0 + (to - from) / 2.0 * sin(TAU * phase);
+                wrap_angle(-target - angle) * RESPONSE
+            }
+            SpinMode::Stepped => 0.0,
+        }
+    }
+
+    pub fn step_angle(&self, beats: f32) -> Option<f32> {
+        if self.modulation.mode != SpinMode::Stepped {
+            return None;
+        }
+
+        let step = TAU / self.pads.len().max(1) as f32;
+        Some(wrap_angle(-beats.floor() * step * self.spin.signum()))
+    }
+
+    pub fn channel(&self, pad_index: usize, ball: &BallSize) -> u8 {
+        if let Some(pad) = self.pads.get(pad_index) {
+            if pad.channel.enabled {
+                return pad.channel.value;
+            }
+        }
+
+        if self.channel.enabled {
+            return self.channel.value;
+        }
+
+        ball.channel
+    }
+
+    pub fn fit_to_shape(&mut self) {
+        self.num_sides = self
+            .num_sides
+            .clamp(geometry::MIN_SIDES, geometry::MAX_SIDES);
+        self.vertex_radii.resize(self.num_sides, 1.0);
+
+        if self.shape == Shape::Freeform && self.vertices.len() < geometry::MIN_SIDES {
+            self.vertices = geometry::polygon(self.radius, self.num_sides)
+                .into_iter()
+                .map(|vertex| (vertex.x, vertex.y))
+                .collect();
+        }
+
+        let num_sides = self.vertices().len();
+        self.pads.resize(num_sides, PadSettings::default());
+    }
+
+    pub fn fits_shape(&self) -> bool {
+        let num_sides = self.vertices().len();
+
+        (geometry::MIN_SIDES..=geometry::MAX_SIDES).contains(&self.num_sides)
+            && self.vertex_radii.len() == self.num_sides
+            && (self.shape != Shape::Freeform || self.vertices.len() >= geometry::MIN_SIDES)
+            && self.pads.len() == num_sides
+    }
+
+    pub fn sides(&self) -> Vec<Side> {
+        self.pads.iter().map(|pad| pad.side).collect()
+    }
+
+    pub fn notes_mut(&mut self) -> impl Iterator<Item = &mut Note> {
+        self.pads.iter_mut().map(|pad| &mut pad.note)
+    }
+}
+
 #[derive(Component)]
-struct Tombola {
-    shape: geometry::Shape,
+pub struct Tombola {
+    pub index: usize,
+    vertices: Vec<Vec2>,
+    sides: Vec<Side>,
+}
+
+#[derive(Resource, Default)]
+pub struct ShapeEditor {
+    pub tombola: Option<usize>,
+    dragging: Option<usize>,
+}
+
+impl ShapeEditor {
+    pub fn is_editing(&self) -> bool {
+        self.tombola.is_some()
+    }
+
+    pub fn update(
+        &mut self,
+        settings: &mut Settings,
+        mouse: Vec2,
+        buttons: &ButtonInput<MouseButton>,
+    ) {
+        const HANDLE_RADIUS: f32 = 12.0;
+
+        let tombola = self
+            .tombola
+            .and_then(|index| settings.world.tombolas.get_mut(index))
+            .filter(|tombola| tombola.shape == Shape::Freeform);
+
+        if tombola.is_none() {
+            self.tombola = None;
+            self.dragging = None;
+        }
+
+        if let Some(tombola) = tombola {
+            let local = mouse - tombola.position();
+
+            if buttons.just_pressed(MouseButton::Left) {
+                self.dragging = tombola.vertices.iter().position(|vertex| {
+                    Vec2::new(vertex.0, vertex.1).distance(local) < HANDLE_RADIUS
+                });
+            }
+
+            if buttons.just_released(MouseButton::Left) {
+                self.dragging = None;
+            }
+
+            if let Some(vertex) = self
+                .dragging
+                .and_then(|index| tombola.vertices.get_mut(index))
+            {
+                *vertex = (local.x, local.y);
+            }
+        }
+    }
+}
+
+#[derive(Event)]
+pub struct PadHit {
+    pub tombola: usize,
+    pub pad: usize,
+    pub note: u8,
+    pub velocity: u8,
+    pub size: usize,
+    pub position: Vec2,
+}
+
+struct NoteTrigger {
+    pad: Entity,
+    ball: Entity,
+    pitches: Vec<u8>,
+    velocity: u8,
+    channel: u8,
+    duration: Duration,
+    sends_midi: bool,
+    sample: Option<PlaySample>,
 }
 
+#[derive(Resource, Default)]
+struct QuantizedNotes(Vec<(u64, NoteTrigger)>);
+
 fn spawn_tombola(
     commands: &mut Commands,
-    mut meshes: &mut ResMut<Assets<Mesh>>,
-    mut materials: &mut ResMut<Assets<ColorMaterial>>,
-    shape: geometry::Shape,
-    spin: f32,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    index: usize,
+    tombola: &TombolaSettings,
     bounciness: f32,
-    notes: &Vec<Note>,
 ) {
     const THICKNESS: f32 = 5.0;
-    const APOTHEM: f32 = 225.0;
 
-    let side_length = 2.0 * APOTHEM * tan(PI / shape.get_num_sides() as f32);
-    let size = Vec2::new(side_length, THICKNESS);
-    let position = Vec2::new(0.0, 0.0);
+    let vertices = tombola.vertices();
+    let position = tombola.position();
 
     commands
         .spawn((
-            Tombola { shape },
+            Tombola {
+                index,
+                vertices: vertices.clone(),
+                sides: tombola.sides(),
+            },
             RigidBody::Kinematic,
-            AngularVelocity(-spin),
+            AngularVelocity(-tombola.spin),
             Transform::from_xyz(position.x, position.y, 0.0),
             Visibility::default(),
         ))
         .with_children(|commands| {
-            let transforms = shape.get_side_transforms(position, APOTHEM);
-            for (index, transform) in transforms.into_iter().enumerate() {
-                commands
-                    .spawn((
-                        PadBundle::new(
-                            index,
-                            Vec2::new(size.x + (THICKNESS / 2.0), size.y),
-                            transform,
-                            notes[index],
-                            bounciness,
-                            &mut meshes,
-                            &mut materials,
-                        ),
-                        CollisionEventsEnabled,
-                    ))
-                    .observe(on_pad_collision);
+            let edges = geometry::edges(&vertices);
+            for (pad_index, (transform, length)) in edges.into_iter().enumerate() {
+                let side_size = Vec2::new(length + (THICKNESS / 2.0), THICKNESS);
+
+                match tombola.pads[pad_index].side {
+                    Side::Pad => {
+                        commands
+                            .spawn((
+                                PadBundle::new(
+                                    index,
+                                    pad_index,
+                                    side_size,
+                                    transform,
+                                    tombola.pads[pad_index].note,
+                                    bounciness,
+                                    meshes,
+                                    materials,
+                                ),
+                                CollisionEventsEnabled,
+                            ))
+                            .observe(on_pad_collision);
+                    }
+                    Side::Wall => {
+                        commands.spawn(WallBundle::new(
+                            side_size, transform, bounciness, meshes, materials,
+                        ));
+                    }
+                    Side::Open => {}
+                }
             }
         });
 }
 
+fn play_note(
+    pad: &mut Pad,
+    trigger: &NoteTrigger,
+    midi: &mut EventWriter<MidiOutputEvent>,
+    samples: &mut EventWriter<PlaySample>,
+) {
+    if let Some(sample) = &trigger.sample {
+        samples.write(sample.clone());
+    }
+
+    if !trigger.sends_midi {
+        return;
+    }
+
+    for pitch in trigger.pitches.iter() {
+        if let Some(playing) = pad.playing_notes.get(pitch) {
+            midi.write(MidiOutputEvent::NoteOff {
+                pitch: *pitch,
+                channel: playing.channel,
+            });
+        }
+
+        midi.write(MidiOutputEvent::NoteOn {
+            pitch: *pitch,
+            velocity: trigger.velocity,
+            channel: trigger.channel,
+            ball: Some(trigger.ball),
+        });
+
+        pad.playing_notes.insert(
+            *pitch,
+            PlayingNote {
+                channel: trigger.channel,
+                timer: Timer::new(trigger.duration, TimerMode::Once),
+            },
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn on_pad_collision(
     trigger: Trigger<OnCollisionStart>,
-    mut pads: Query<&mut Pad>,
-    mut balls: Query<(&mut Ball, &LinearVelocity)>,
+    mut pads: Query<(&mut Pad, &GlobalTransform, &ChildOf)>,
+    tombolas: Query<(&GlobalTransform, &AngularVelocity), With<Tombola>>,
+    mut balls: Query<(&mut Ball, &LinearVelocity, &GlobalTransform)>,
     mut materials: ResMut<Assets<ColorMaterial>>,
+    mut quantized: ResMut<QuantizedNotes>,
     settings: Res<Settings>,
+    transport: Res<Transport>,
     mut midi: EventWriter<MidiOutputEvent>,
+    mut hits: EventWriter<PadHit>,
+    mut samples: EventWriter<PlaySample>,
 ) {
     let target = trigger.target();
     let collider = trigger.collider;
 
-    if let Ok(mut pad) = pads.get_mut(target) {
-        if let Ok((mut ball, velocity)) = balls.get_mut(collider) {
-            if pad.playing_notes.contains_key(&ball.size.to_octave()) {
-                midi.write(MidiOutputEvent::NoteOff {
-                    note: pad.note,
-                    octave: ball.size.to_octave(),
-                });
-            }
-
-            midi.write(MidiOutputEvent::NoteOn {
-                note: pad.note,
-                octave: ball.size.to_octave(),
-                velocity: if settings.midi.fixed_note_velocity.enabled {
+    if let Ok((mut pad, pad_transform, parent)) = pads.get_mut(target) {
+        if let Ok((mut ball, velocity, ball_transform)) = balls.get_mut(collider) {
+            if let Some(tombola) = settings.world.tombolas.get(pad.tombola) {
+                let ball_size = settings.world.ball_size(ball.size);
+                let pad_sample = tombola
+                    .pads
+                    .get(pad.index)
+                    .map(|pad_settings| pad_settings.sample.clone())
+                    .unwrap_or_default();
+                let speed = match tombolas.get(parent.parent()) {
+                    Ok((tombola_transform, angular_velocity)) => impact_speed(
+                        velocity.0,
+                        ball_transform.translation().truncate(),
+                        tombola_transform.translation().truncate(),
+                        angular_velocity.0,
+                        (pad_transform.rotation() * Vec3::Y).truncate(),
+                    ),
+                    Err(_) => velocity.length(),
+                };
+                let velocity = if settings.midi.fixed_note_velocity.enabled {
                     settings.midi.fixed_note_velocity.value
                 } else {
-                    midi::to_velocity(velocity.length())
-                },
-            });
-
-            pad.playing_notes.insert(
-                ball.size.to_octave(),
-                Timer::new(
-                    if settings.midi.fixed_note_length.enabled {
+                    settings.midi.velocity.to_velocity(speed)
+                };
+                let note = NoteTrigger {
+                    pad: target,
+                    ball: collider,
+                    pitches: settings
+                        .midi
+                        .pitches(pad.note, &ball_size, tombola, pad.index),
+                    velocity,
+                    channel: tombola.channel(pad.index, &ball_size),
+                    duration: if settings.midi.fixed_note_length.enabled {
                         Duration::from_millis(settings.midi.fixed_note_length.value)
                     } else {
-                        midi::to_note_duration(velocity.length())
+                        midi::to_note_duration(speed)
                     },
-                    TimerMode::Once,
-                ),
-            );
+                    sends_midi: pad_sample.sends_midi(),
+                    sample: pad_sample.trigger(&ball_size, velocity),
+                };
+
+                hits.write(PadHit {
+                    tombola: pad.tombola,
+                    pad: pad.index,
+                    note: note.pitches.first().copied().unwrap_or_default(),
+                    velocity: note.velocity,
+                    size: ball.size,
+                    position: ball_transform.translation().truncate(),
+                });
 
-            if let Some(material) = materials.get_mut(pad.material.0.id()) {
-                material.color = Pad::hit_color();
+                match transport.next_boundary(settings.midi.quantize) {
+                    Some(boundary) => quantized.0.push((boundary, note)),
+                    None => play_note(&mut pad, &note, &mut midi, &mut samples),
+                }
+
+                if let Some(material) = materials.get_mut(pad.material.0.id()) {
+                    material.color = Pad::hit_color();
+                }
+
+                ball.bounces += 1;
             }
+        }
+    }
+}
+
+fn release_quantized_notes(
+    mut quantized: ResMut<QuantizedNotes>,
+    mut pads: Query<&mut Pad>,
+    transport: Res<Transport>,
+    mut midi: EventWriter<MidiOutputEvent>,
+    mut samples: EventWriter<PlaySample>,
+) {
+    if !transport.is_playing() {
+        quantized.0.clear();
+        return;
+    }
 
-            ball.bounces += 1;
+    let (due, waiting): (Vec<_>, Vec<_>) = quantized
+        .0
+        .drain(..)
+        .partition(|(boundary, _)| transport.is_due(*boundary));
+    quantized.0 = waiting;
+
+    for (_, note) in due {
+        if let Ok(mut pad) = pads.get_mut(note.pad) {
+            play_note(&mut pad, &note, &mut midi, &mut samples);
         }
     }
 }
 
-fn spawn_default_tombola(
+fn spawn_tombolas(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
-    settings: Res<Settings>,
+    mut settings: ResMut<Settings>,
 ) {
-    spawn_tombola(
-        &mut commands,
-        &mut meshes,
-        &mut materials,
-        settings.world.tombola_shape,
-        settings.world.tombola_spin,
-        settings.world.bounciness,
-        &settings.midi.tombola_notes,
-    );
-}
-
-fn update_tombola_shape(
+    settings.fit_to_shapes();
+
+    for (index, tombola) in settings.world.tombolas.iter().enumerate() {
+        spawn_tombola(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            index,
+            tombola,
+            settings.world.bounciness,
+        );
+    }
+}
+
+fn despawn_tombola(
+    commands: &mut Commands,
+    entity: Entity,
+    index: usize,
+    pads: &mut Query<&mut Pad>,
+    midi: &mut EventWriter<MidiOutputEvent>,
+) {
+    for mut pad in pads.iter_mut().filter(|pad| pad.tombola == index) {
+        for (pitch, playing) in pad.playing_notes.drain() {
+            midi.write(MidiOutputEvent::NoteOff {
+                pitch,
+                channel: playing.channel,
+            });
+        }
+    }
+
+    commands.entity(entity).despawn();
+}
+
+fn update_tombolas(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     mut settings: ResMut<Settings>,
-    tombola: Query<(Entity, &Tombola)>,
+    mut pads: Query<&mut Pad>,
+    mut midi: EventWriter<MidiOutputEvent>,
+    tombolas: Query<(Entity, &Tombola)>,
 ) {
-    if let Ok((entity, tombola)) = tombola.single() {
-        if tombola.shape != settings.world.tombola_shape {
-            commands.entity(entity).despawn();
+    let fits_shapes = settings
+        .world
+        .tombolas
+        .iter()
+        .all(TombolaSettings::fits_shape);
+    if !fits_shapes {
+        settings.fit_to_shapes();
+    }
 
-            let num_sides = settings.world.tombola_shape.get_num_sides();
-            settings.midi.tombola_notes.resize(num_sides, Note::C);
+    if tombolas.iter().count() != settings.world.tombolas.len() {
+        for (entity, tombola) in tombolas.iter() {
+            despawn_tombola(&mut commands, entity, tombola.index, &mut pads, &mut midi);
+        }
 
+        for (index, tombola) in settings.world.tombolas.iter().enumerate() {
             spawn_tombola(
                 &mut commands,
                 &mut meshes,
                 &mut materials,
-                settings.world.tombola_shape,
-                settings.world.tombola_spin,
+                index,
+                tombola,
                 settings.world.bounciness,
-                &settings.midi.tombola_notes,
             );
         }
+
+        return;
+    }
+
+    for (entity, tombola) in tombolas.iter() {
+        let bounciness = settings.world.bounciness;
+        if let Some(tombola_settings) = settings.world.tombolas.get(tombola.index) {
+            if tombola.vertices != tombola_settings.vertices()
+                || tombola.sides != tombola_settings.sides()
+            {
+                despawn_tombola(&mut commands, entity, tombola.index, &mut pads, &mut midi);
+
+                spawn_tombola(
+                    &mut commands,
+                    &mut meshes,
+                    &mut materials,
+                    tombola.index,
+                    tombola_settings,
+                    bounciness,
+                );
+            }
+        }
     }
 }
 
-fn update_tombola_spin(
-    mut spin: Query<&mut AngularVelocity, With<Tombola>>,
+fn wrap_angle(angle: f32) -> f32 {
+    (angle + PI).rem_euclid(TAU) - PI
+}
+
+fn update_tombola_motion(
+    mut tombolas: Query<(&Tombola, &mut Transform, &mut AngularVelocity)>,
     settings: Res<Settings>,
+    editor: Res<ShapeEditor>,
+    transport: Res<Transport>,
+    time: Res<Time>,
 ) {
-    if let Ok(mut tombola) = spin.single_mut() {
-        tombola.0 = -settings.world.tombola_spin;
+    let beats = transport.pulse() as f32 / midi::CLOCKS_PER_QUARTER as f32;
+
+    for (tombola, mut transform, mut spin) in tombolas.iter_mut() {
+        if let Some(tombola_settings) = settings.world.tombolas.get(tombola.index) {
+            let position = tombola_settings.position();
+            if transform.translation.truncate() != position {
+                transform.translation.x = position.x;
+                transform.translation.y = position.y;
+            }
+
+            if editor.tombola == Some(tombola.index) {
+                transform.rotation = Quat::IDENTITY;
+                spin.0 = 0.0;
+            } else if let Some(step_angle) = tombola_settings.step_angle(beats) {
+                let rotation = Quat::from_rotation_z(step_angle);
+                if transform.rotation != rotation {
+                    transform.rotation = rotation;
+                }
+                spin.0 = 0.0;
+            } else {
+                let angle = transform.rotation.to_euler(EulerRot::ZYX).0;
+                spin.0 = tombola_settings.angular_velocity(angle, time.elapsed_secs());
+            }
+        }
+    }
+}
+
+fn draw_shape_editor(mut gizmos: Gizmos, settings: Res<Settings>, editor: Res<ShapeEditor>) {
+    const HANDLE_RADIUS: f32 = 8.0;
+
+    if let Some(tombola) = editor
+        .tombola
+        .and_then(|index| settings.world.tombolas.get(index))
+    {
+        let position = tombola.position();
+        for (index, vertex) in tombola.vertices().into_iter().enumerate() {
+            let color = if editor.dragging == Some(index) {
+                Color::linear_rgb(1.5, 1.5, 1.5)
+            } else {
+                Color::linear_rgb(0.6, 0.6, 0.6)
+            };
+            gizmos.circle_2d(position + vertex, HANDLE_RADIUS, color);
+        }
     }
 }
 
 fn update_tombola_notes(mut pads: Query<&mut Pad>, settings: Res<Settings>) {
     for mut pad in pads.iter_mut() {
-        pad.note = settings.midi.tombola_notes[pad.index];
+        if let Some(tombola) = settings.world.tombolas.get(pad.tombola) {
+            if let Some(pad_settings) = tombola.pads.get(pad.index) {
+                pad.note = pad_settings.note;
+            }
+        }
     }
 }
 
@@ -211,17 +741,15 @@ fn note_off_pads(
     mut midi: EventWriter<MidiOutputEvent>,
 ) {
     for mut pad in pads.iter_mut() {
-        let note = pad.note.clone();
-
-        for (_, timer) in pad.playing_notes.iter_mut() {
-            timer.tick(time.delta());
+        for (_, playing) in pad.playing_notes.iter_mut() {
+            playing.timer.tick(time.delta());
         }
 
-        pad.playing_notes.retain(|octave, timer| {
-            if timer.just_finished() {
+        pad.playing_notes.retain(|pitch, playing| {
+            if playing.timer.just_finished() {
                 midi.write(MidiOutputEvent::NoteOff {
-                    note,
-                    octave: octave.clone(),
+                    pitch: *pitch,
+                    channel: playing.channel,
                 });
                 return false;
             }
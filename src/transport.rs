@@ -0,0 +1,368 @@
+use crate::midi;
+use crate::midi::{MidiInputEvent, MidiOutputEvent};
+use crate::pad::Pad;
+use crate::settings::Settings;
+use avian2d::prelude::{Physics, PhysicsTime};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+use strum_macros::EnumIter;
+
+const CLOCK_TIMEOUT: Duration = Duration::from_millis(500);
+
+pub struct TransportPlugin;
+
+impl Plugin for TransportPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Transport>();
+        app.add_systems(
+            Update,
+            (
+                follow_external_clock,
+                update_transport.after(follow_external_clock),
+                advance_internal_clock.after(update_transport),
+            ),
+        );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, EnumIter, Serialize, Deserialize)]
+pub enum ClockSource {
+    Internal,
+    External,
+}
+
+impl fmt::Display for ClockSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClockSource::Internal => write!(f, "Internal"),
+            ClockSource::External => write!(f, "External"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, EnumIter, Serialize, Deserialize)]
+pub enum Quantize {
+    Off,
+    Quarter,
+    Eighth,
+    Sixteenth,
+    ThirtySecond,
+    QuarterTriplet,
+    EighthTriplet,
+    SixteenthTriplet,
+}
+
+impl Quantize {
+    pub fn to_pulses(self) -> Option<u64> {
+        match self {
+            Quantize::Off => None,
+            Quantize::Quarter => Some(24),
+            Quantize::Eighth => Some(12),
+            Quantize::Sixteenth => Some(6),
+            Quantize::ThirtySecond => Some(3),
+            Quantize::QuarterTriplet => Some(16),
+            Quantize::EighthTriplet => Some(8),
+            Quantize::SixteenthTriplet => Some(4),
+        }
+    }
+}
+
+impl fmt::Display for Quantize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Quantize::Off => write!(f, "Off"),
+            Quantize::Quarter => write!(f, "1/4"),
+            Quantize::Eighth => write!(f, "1/8"),
+            Quantize::Sixteenth => write!(f, "1/16"),
+            Quantize::ThirtySecond => write!(f, "1/32"),
+            Quantize::QuarterTriplet => write!(f, "1/4T"),
+            Quantize::EighthTriplet => write!(f, "1/8T"),
+            Quantize::SixteenthTriplet => write!(f, "1/16T"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum TransportCommand {
+    Start,
+    Continue,
+    Stop,
+}
+
+#[derive(Resource)]
+pub struct Transport {
+    playing: bool,
+    clock_enabled: bool,
+    pending: Option<TransportCommand>,
+    clock_phase: f64,
+    pulse: u64,
+    last_external_clock: Option<u64>,
+    last_clock_received: Option<Duration>,
+    clock_stalled: bool,
+    external_intervals: VecDeque<u64>,
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Transport {
+            playing: true,
+            clock_enabled: false,
+            pending: None,
+            clock_phase: 0.0,
+            pulse: 0,
+            last_external_clock: None,
+            last_clock_received: None,
+            clock_stalled: false,
+            external_intervals: VecDeque::new(),
+        }
+    }
+}
+
+impl Transport {
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn pulse(&self) -> u64 {
+        self.pulse
+    }
+
+    pub fn next_boundary(&self, quantize: Quantize) -> Option<u64> {
+        if self.clock_stalled {
+            return None;
+        }
+
+        quantize
+            .to_pulses()
+            .map(|grid| (self.pulse / grid + 1) * grid)
+    }
+
+    pub fn is_due(&self, boundary: u64) -> bool {
+        self.clock_stalled || boundary <= self.pulse
+    }
+
+    pub fn external_bpm(&self) -> Option<f32> {
+        if self.external_intervals.is_empty() {
+            return None;
+        }
+
+        let total: u64 = self.external_intervals.iter().sum();
+        let average_micros = total as f64 / self.external_intervals.len() as f64;
+        let micros_per_quarter = average_micros * midi::CLOCKS_PER_QUARTER as f64;
+
+        Some((60_000_000.0 / micros_per_quarter) as f32)
+    }
+
+    pub fn start(&mut self) {
+        self.pending = Some(TransportCommand::Start);
+    }
+
+    pub fn resume(&mut self) {
+        self.pending = Some(TransportCommand::Continue);
+    }
+
+    pub fn stop(&mut self) {
+        self.pending = Some(TransportCommand::Stop);
+    }
+}
+
+fn flush_playing_notes(pads: &mut Query<&mut Pad>, midi: &mut EventWriter<MidiOutputEvent>) {
+    for mut pad in pads.iter_mut() {
+        for (pitch, playing) in pad.playing_notes.drain() {
+            midi.write(MidiOutputEvent::NoteOff {
+                pitch,
+                channel: playing.channel,
+            });
+        }
+    }
+}
+
+fn follow_external_clock(
+    mut transport: ResMut<Transport>,
+    mut input: EventReader<MidiInputEvent>,
+    settings: Res<Settings>,
+    time: Res<Time<Real>>,
+) {
+    const TEMPO_WINDOW: usize = midi::CLOCKS_PER_QUARTER as usize;
+
+    let now = time.elapsed();
+
+    for event in input.read() {
+        if settings.midi.clock.source != ClockSource::External {
+            continue;
+        }
+
+        match event.message.first() {
+            Some(&midi::CLOCK) => {
+                if let Some(last) = transport.last_external_clock {
+                    transport
+                        .external_intervals
+                        .push_back(event.timestamp.saturating_sub(last));
+                    if transport.external_intervals.len() > TEMPO_WINDOW {
+                        transport.external_intervals.pop_front();
+                    }
+                }
+                transport.last_external_clock = Some(event.timestamp);
+                transport.last_clock_received = Some(now);
+
+                if transport.playing {
+                    transport.pulse += 1;
+                }
+            }
+            Some(&midi::START) => transport.start(),
+            Some(&midi::CONTINUE) => transport.resume(),
+            Some(&midi::STOP) => transport.stop(),
+            _ => {}
+        }
+    }
+
+    transport.clock_stalled = settings.midi.clock.source == ClockSource::External
+        && transport
+            .last_clock_received
+            .is_none_or(|received| now - received > CLOCK_TIMEOUT);
+}
+
+fn update_transport(
+    mut transport: ResMut<Transport>,
+    mut physics_time: ResMut<Time<Physics>>,
+    mut pads: Query<&mut Pad>,
+    mut midi: EventWriter<MidiOutputEvent>,
+    settings: Res<Settings>,
+) {
+    let clock_enabled =
+        settings.midi.clock.enabled && settings.midi.clock.source == ClockSource::Internal;
+
+    if clock_enabled != transport.clock_enabled {
+        transport.clock_enabled = clock_enabled;
+
+        if transport.playing {
+            midi.write(if transport.clock_enabled {
+                MidiOutputEvent::Start
+            } else {
+                MidiOutputEvent::Stop
+            });
+            transport.clock_phase = 0.0;
+        }
+    }
+
+    if let Some(command) = transport.pending.take() {
+        match command {
+            TransportCommand::Start | TransportCommand::Continue => {
+                if command == TransportCommand::Start {
+                    transport.clock_phase = 0.0;
+                    transport.pulse = 0;
+                }
+
+                if transport.clock_enabled {
+                    midi.write(if command == TransportCommand::Start {
+                        MidiOutputEvent::Start
+                    } else {
+                        MidiOutputEvent::Continue
+                    });
+                }
+
+                physics_time.unpause();
+                transport.playing = true;
+            }
+            TransportCommand::Stop => {
+                if transport.clock_enabled {
+                    midi.write(MidiOutputEvent::Stop);
+                }
+
+                physics_time.pause();
+                flush_playing_notes(&mut pads, &mut midi);
+                transport.playing = false;
+            }
+        }
+    }
+}
+
+pub fn advance_internal_clock(
+    mut transport: ResMut<Transport>,
+    mut midi: EventWriter<MidiOutputEvent>,
+    settings: Res<Settings>,
+    time: Res<Time<Real>>,
+) {
+    if !transport.playing || settings.midi.clock.source != ClockSource::Internal {
+        return;
+    }
+
+    let pulses_per_second = settings.midi.clock.bpm as f64 / 60.0 * midi::CLOCKS_PER_QUARTER as f64;
+    transport.clock_phase += time.delta_secs_f64() * pulses_per_second;
+
+    while transport.clock_phase >= 1.0 {
+        if transport.clock_enabled {
+            midi.write(MidiOutputEvent::Clock);
+        }
+        transport.clock_phase -= 1.0;
+        transport.pulse += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use strum::IntoEnumIterator;
+
+    fn at_pulse(pulse: u64) -> Transport {
+        Transport { pulse, ..default() }
+    }
+
+    #[test]
+    fn quantize_grids_divide_a_bar() {
+        const PULSES_PER_BAR: u64 = 4 * midi::CLOCKS_PER_QUARTER as u64;
+
+        assert_eq!(Quantize::Off.to_pulses(), None);
+        assert_eq!(
+            Quantize::Quarter.to_pulses(),
+            Some(midi::CLOCKS_PER_QUARTER as u64)
+        );
+        assert_eq!(Quantize::QuarterTriplet.to_pulses(), Some(16));
+        for quantize in Quantize::iter().filter(|quantize| *quantize != Quantize::Off) {
+            let grid = quantize.to_pulses().unwrap();
+            assert_eq!(PULSES_PER_BAR % grid, 0);
+        }
+    }
+
+    #[test]
+    fn next_boundary_is_always_ahead() {
+        assert_eq!(at_pulse(0).next_boundary(Quantize::Quarter), Some(24));
+        assert_eq!(at_pulse(23).next_boundary(Quantize::Quarter), Some(24));
+        assert_eq!(at_pulse(24).next_boundary(Quantize::Quarter), Some(48));
+        assert_eq!(at_pulse(25).next_boundary(Quantize::Sixteenth), Some(30));
+        assert_eq!(at_pulse(25).next_boundary(Quantize::Off), None);
+    }
+
+    #[test]
+    fn stalled_clock_releases_immediately() {
+        let mut transport = at_pulse(10);
+        assert_eq!(transport.next_boundary(Quantize::Eighth), Some(12));
+        assert!(!transport.is_due(12));
+
+        transport.clock_stalled = true;
+        assert_eq!(transport.next_boundary(Quantize::Eighth), None);
+        assert!(transport.is_due(12));
+    }
+
+    #[test]
+    fn due_on_and_after_the_boundary() {
+        assert!(!at_pulse(23).is_due(24));
+        assert!(at_pulse(24).is_due(24));
+        assert!(at_pulse(30).is_due(24));
+    }
+
+    #[test]
+    fn external_bpm_averages_clock_intervals() {
+        let mut transport = Transport::default();
+        assert_eq!(transport.external_bpm(), None);
+
+        transport.external_intervals.push_back(20_833);
+        assert!((transport.external_bpm().unwrap() - 120.0).abs() < 0.01);
+
+        transport.external_intervals.push_back(62_500);
+        assert!((transport.external_bpm().unwrap() - 60.0).abs() < 0.01);
+    }
+}
@@ -1,8 +1,33 @@
 use crate::settings::Settings;
-use crate::size::Size;
+use crate::size;
+use crate::size::BallSize;
+use crate::synth;
+use crate::synth::{Patch, SynthOutput, Waveform};
 
+use crate::chord::{Chord, Voicing};
+use crate::collision::CollisionNote;
+use crate::emitter::{Division, EmitterSettings};
+use crate::geometry;
 use crate::geometry::Shape;
+use crate::keyboard::KeyboardMode;
+use crate::learn::Parameter;
 use crate::midi;
+use crate::modulation;
+use crate::modulation::{Destination, ModulationSettings, Source};
+use crate::mpe;
+use crate::mpe::Zone;
+use crate::preset;
+use crate::preset::Presets;
+use crate::recorder;
+use crate::recorder::Recorder;
+use crate::sampler;
+use crate::sampler::SampleMode;
+use crate::scale;
+use crate::scale::Scale;
+use crate::tombola::{ShapeEditor, Side, SpinMode, TombolaSettings};
+use crate::transport::{ClockSource, Quantize, Transport};
+use crate::velocity;
+use crate::velocity::{Curve, VelocityCurve};
 use bevy::asset::Assets;
 use bevy::color::Color;
 use bevy::math::{Rect, Vec2};
@@ -12,22 +37,22 @@ use strum::IntoEnumIterator;
 
 #[derive(Component)]
 pub struct Cursor {
-    pub size: Size,
+    pub size: usize,
 }
 
 impl Cursor {
-    pub fn get_mesh(&self, meshes: &mut ResMut<Assets<Mesh>>) -> Mesh2d {
-        Mesh2d(meshes.add(Circle::new(self.size.to_radius())))
+    pub fn get_mesh(ball_size: &BallSize, meshes: &mut ResMut<Assets<Mesh>>) -> Mesh2d {
+        Mesh2d(meshes.add(Circle::new(ball_size.radius)))
     }
 
     pub fn get_material(
-        &self,
+        ball_size: &BallSize,
         materials: &mut ResMut<Assets<ColorMaterial>>,
     ) -> MeshMaterial2d<ColorMaterial> {
         const CURSOR_OPACITY: f32 = 0.05;
 
         MeshMaterial2d(materials.add(ColorMaterial::from_color(
-            self.size.to_color().with_alpha(CURSOR_OPACITY),
+            ball_size.to_color().with_alpha(CURSOR_OPACITY),
         )))
     }
 }
@@ -43,17 +68,17 @@ pub struct CursorBundle {
 
 impl CursorBundle {
     pub fn new(
-        size: Size,
+        size: usize,
+        ball_size: &BallSize,
         position: Vec2,
         meshes: &mut ResMut<Assets<Mesh>>,
         materials: &mut ResMut<Assets<ColorMaterial>>,
     ) -> Self {
-        let cursor = Cursor { size };
-        let mesh = cursor.get_mesh(meshes);
-        let material = cursor.get_material(materials);
+        let mesh = Cursor::get_mesh(ball_size, meshes);
+        let material = Cursor::get_material(ball_size, materials);
 
         CursorBundle {
-            marker: cursor,
+            marker: Cursor { size },
             transform: Transform::from_xyz(position.x, position.y, 1.0),
             mesh,
             material,
@@ -64,12 +89,19 @@ impl CursorBundle {
 
 #[derive(Component)]
 pub struct BallSelector {
-    pub size: Size,
+    pub size: usize,
+    pub radius: f32,
+    pub color: Color,
 }
 
 impl BallSelector {
     pub fn hitbox_size() -> f32 {
-        Size::Large.to_radius() + 10.0
+        const PADDING: f32 = 10.0;
+        size::MAX_RADIUS + PADDING
+    }
+
+    pub fn matches(&self, ball_size: &BallSize) -> bool {
+        self.radius == ball_size.radius && self.color == ball_size.to_color()
     }
 }
 
@@ -83,16 +115,23 @@ pub struct BallSelectorBundle {
 
 impl BallSelectorBundle {
     pub fn new(
-        size: Size,
+        size: usize,
+        ball_size: &BallSize,
         position: Vec2,
         meshes: &mut ResMut<Assets<Mesh>>,
         materials: &mut ResMut<Assets<ColorMaterial>>,
     ) -> Self {
         BallSelectorBundle {
-            marker: BallSelector { size },
+            marker: BallSelector {
+                size,
+                radius: ball_size.radius,
+                color: ball_size.to_color(),
+            },
             transform: Transform::from_xyz(position.x, position.y, 1.0),
-            mesh: Mesh2d(meshes.add(Circle::new(size.to_radius()))),
-            material: MeshMaterial2d(materials.add(ColorMaterial::from_color(size.to_color()))),
+            mesh: Mesh2d(meshes.add(Circle::new(ball_size.radius))),
+            material: MeshMaterial2d(
+                materials.add(ColorMaterial::from_color(ball_size.to_color())),
+            ),
         }
     }
 }
@@ -127,7 +166,7 @@ impl HighlightBundle {
 
 pub fn find_selector_position(
     selectors: &Vec<(&BallSelector, &Transform)>,
-    selected: Size,
+    selected: usize,
 ) -> Option<Vec2> {
     if let Some((_found, transform)) = selectors
         .iter()
@@ -139,7 +178,7 @@ pub fn find_selector_position(
     None
 }
 
-pub fn pick_selector(selectors: &Vec<(&BallSelector, &Transform)>, pos: Vec2) -> Option<Size> {
+pub fn pick_selector(selectors: &Vec<(&BallSelector, &Transform)>, pos: Vec2) -> Option<usize> {
     for (selector, transform) in selectors.iter() {
         let centre = transform.translation.truncate();
 
@@ -158,51 +197,326 @@ pub fn pick_selector(selectors: &Vec<(&BallSelector, &Transform)>, pos: Vec2) ->
     None
 }
 
+#[derive(Default)]
+pub struct SettingsMenu {
+    pub hovered: bool,
+    pub removed_ball_size: Option<usize>,
+}
+
 pub fn show_settings_menu(
     mut egui: EguiContexts,
     settings: &mut Settings,
     midi_config: &mut midi::MidiConfig,
-) -> bool {
+    presets: &mut Presets,
+    recorder: &mut Recorder,
+    transport: &mut Transport,
+    shape_editor: &mut ShapeEditor,
+) -> SettingsMenu {
+    let mut removed_ball_size = None;
+
     if let Some(ctx) = egui.try_ctx_mut() {
         egui::Window::new("Settings")
             .default_open(false)
             .show(ctx, |ui| {
-                ui.collapsing("World", |ui| {
-                    ui.label("Shape");
-                    egui::ComboBox::from_id_salt("shape")
-                        .selected_text(settings.world.tombola_shape.to_string())
-                        .show_ui(ui, |ui| {
-                            for shape in Shape::iter() {
-                                ui.selectable_value(
-                                    &mut settings.world.tombola_shape,
-                                    shape,
-                                    shape.to_string(),
+                ui.collapsing("Presets", |ui| {
+                    ui.label(format!(
+                        "Current: {}",
+                        presets
+                            .current_name()
+                            .unwrap_or_else(|| "Unsaved".to_string())
+                    ));
+
+                    if let Some(path) = presets.current.clone() {
+                        if ui.button("Save").clicked() {
+                            if let Err(error) = preset::save(settings, &path) {
+                                println!("Failed to save preset {}: {}", path.display(), error);
+                            }
+                        }
+                    }
+                    ui.add_space(10.0);
+
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_salt("presets")
+                            .selected_text(presets.selected.clone())
+                            .show_ui(ui, |ui| {
+                                for name in preset::list() {
+                                    ui.selectable_value(&mut presets.selected, name.clone(), name);
+                                }
+                            });
+
+                        if ui.button("Load").clicked() && !presets.selected.is_empty() {
+                            let path = preset::path_for(&presets.selected);
+                            match preset::load(&path) {
+                                Ok(loaded) => {
+                                    *settings = loaded;
+                                    presets.current = Some(path);
+                                }
+                                Err(error) => {
+                                    println!("Failed to load preset {}: {}", path.display(), error)
+                                }
+                            }
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut presets.new_name);
+
+                        if ui.button("Save As").clicked() && !presets.new_name.trim().is_empty() {
+                            let path = preset::path_for(presets.new_name.trim());
+                            match preset::save(settings, &path) {
+                                Ok(()) => {
+                                    presets.selected = preset::name_of(&path);
+                                    presets.current = Some(path);
+                                    presets.new_name.clear();
+                                }
+                                Err(error) => {
+                                    println!("Failed to save preset {}: {}", path.display(), error)
+                                }
+                            }
+                        }
+                    });
+                });
+                ui.collapsing("Tombolas", |ui| {
+                    let mut removed = None;
+                    for (index, tombola) in settings.world.tombolas.iter_mut().enumerate() {
+                        ui.push_id(("tombola", index), |ui| {
+                            ui.horizontal(|ui| {
+                                ui.label(format!("Tombola {}", index + 1));
+
+                                egui::ComboBox::from_id_salt("shape")
+                                    .selected_text(tombola.shape.to_string())
+                                    .show_ui(ui, |ui| {
+                                        for shape in Shape::iter() {
+                                            ui.selectable_value(
+                                                &mut tombola.shape,
+                                                shape,
+                                                shape.to_string(),
+                                            );
+                                        }
+                                    });
+
+                                if ui.button("Remove").clicked() {
+                                    removed = Some(index);
+                                }
+                            });
+
+                            ui.horizontal(|ui| {
+                                ui.label("Position");
+                                ui.add(egui::DragValue::new(&mut tombola.position.0).prefix("x: "));
+                                ui.add(egui::DragValue::new(&mut tombola.position.1).prefix("y: "));
+                            });
+                            match tombola.shape {
+                                Shape::Polygon => {
+                                    ui.add(
+                                        egui::Slider::new(
+                                            &mut tombola.num_sides,
+                                            geometry::MIN_SIDES..=geometry::MAX_SIDES,
+                                        )
+                                        .text("Sides"),
+                                    );
+                                }
+                                Shape::Star => {
+                                    ui.add(
+                                        egui::Slider::new(
+                                            &mut tombola.star_points,
+                                            geometry::MIN_SIDES..=geometry::MAX_SIDES / 2,
+                                        )
+                                        .text("Points"),
+                                    );
+                                    ui.add(
+                                        egui::Slider::new(&mut tombola.star_depth, 0.1..=0.95)
+                                            .text("Depth")
+                                            .fixed_decimals(2),
+                                    );
+                                }
+                                Shape::Irregular => {
+                                    ui.add(
+                                        egui::Slider::new(
+                                            &mut tombola.num_sides,
+                                            geometry::MIN_SIDES..=geometry::MAX_SIDES,
+                                        )
+                                        .text("Sides"),
+                                    );
+                                    ui.label("Vertex Radius");
+                                    ui.horizontal_wrapped(|ui| {
+                                        for radius in tombola.vertex_radii.iter_mut() {
+                                            ui.add(
+                                                egui::DragValue::new(radius)
+                                                    .range(0.2..=1.5)
+                                                    .speed(0.01)
+                                                    .fixed_decimals(2),
+                                            );
+                                        }
+                                    });
+                                }
+                                Shape::Freeform => {
+                                    let mut editing = shape_editor.tombola == Some(index);
+                                    ui.horizontal(|ui| {
+                                        if ui.checkbox(&mut editing, "Edit Vertices").changed() {
+                                            shape_editor.tombola =
+                                                if editing { Some(index) } else { None };
+                                        }
+
+                                        if ui.button("Add Vertex").clicked() {
+                                            if let (Some(first), Some(last)) =
+                                                (tombola.vertices.first(), tombola.vertices.last())
+                                            {
+                                                let vertex = (
+                                                    (first.0 + last.0) / 2.0,
+                                                    (first.1 + last.1) / 2.0,
+                                                );
+                                                tombola.vertices.push(vertex);
+                                            }
+                                        }
+                                        if ui.button("Remove Vertex").clicked()
+                                            && tombola.vertices.len() > geometry::MIN_SIDES
+                                        {
+                                            tombola.vertices.pop();
+                                        }
+                                    });
+                                }
+                                _ => {}
+                            }
+                            if tombola.shape != Shape::Freeform {
+                                ui.add(
+                                    egui::Slider::new(&mut tombola.radius, 50.0..=400.0)
+                                        .text("Size"),
                                 );
                             }
+                            let response = ui
+                                .add(egui::Slider::new(&mut tombola.spin, -2.0..=2.0).text("Spin"));
+                            learnable(
+                                &response,
+                                &mut midi_config.learning,
+                                Parameter::TombolaSpin(index),
+                            );
+                            ui.horizontal(|ui| {
+                                ui.label("Spin Mode");
+                                egui::ComboBox::from_id_salt("spin_mode")
+                                    .selected_text(tombola.modulation.mode.to_string())
+                                    .show_ui(ui, |ui| {
+                                        for mode in SpinMode::iter() {
+                                            ui.selectable_value(
+                                                &mut tombola.modulation.mode,
+                                                mode,
+                                                mode.to_string(),
+                                            );
+                                        }
+                                    });
+                            });
+                            match tombola.modulation.mode {
+                                SpinMode::Constant => {}
+                                SpinMode::Sine | SpinMode::Triangle => {
+                                    ui.add(
+                                        egui::Slider::new(&mut tombola.modulation.rate, 0.01..=2.0)
+                                            .text("Rate")
+                                            .suffix("Hz"),
+                                    );
+                                    ui.add(
+                                        egui::Slider::new(&mut tombola.modulation.depth, 0.0..=4.0)
+                                            .text("Depth"),
+                                    );
+                                }
+                                SpinMode::Pendulum => {
+                                    ui.add(
+                                        egui::Slider::new(&mut tombola.modulation.rate, 0.01..=2.0)
+                                            .text("Rate")
+                                            .suffix("Hz"),
+                                    );
+                                    ui.add(
+                                        egui::Slider::new(
+                                            &mut tombola.modulation.from_angle,
+                                            -180.0..=180.0,
+                                        )
+                                        .text("From")
+                                        .suffix("°"),
+                                    );
+                                    ui.add(
+                                        egui::Slider::new(
+                                            &mut tombola.modulation.to_angle,
+                                            -180.0..=180.0,
+                                        )
+                                        .text("To")
+                                        .suffix("°"),
+                                    );
+                                }
+                                SpinMode::Stepped => {
+                                    ui.label(
+                                        "Turns one side per beat, in the direction of the spin",
+                                    );
+                                }
+                            }
+
+                            ui.label("Sides");
+                            ui.horizontal_wrapped(|ui| {
+                                for (side_index, pad) in tombola.pads.iter_mut().enumerate() {
+                                    let side = &mut pad.side;
+                                    egui::ComboBox::from_id_salt(("side", side_index))
+                                        .width(60.0)
+                                        .selected_text(format!("{}: {}", side_index + 1, side))
+                                        .show_ui(ui, |ui| {
+                                            for option in Side::iter() {
+                                                ui.selectable_value(
+                                                    side,
+                                                    option,
+                                                    option.to_string(),
+                                                );
+                                            }
+                                        });
+                                }
+                            });
+
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut tombola.channel.enabled, "Channel");
+                                if tombola.channel.enabled {
+                                    ui.add(egui::Slider::new(
+                                        &mut tombola.channel.value,
+                                        midi::MIN_CHANNEL..=midi::MAX_CHANNEL,
+                                    ));
+                                }
+                            });
                         });
-                    ui.add_space(10.0);
+                        ui.add_space(10.0);
+                    }
 
-                    ui.add(
-                        egui::Slider::new(&mut settings.world.tombola_spin, -2.0..=2.0)
-                            .text("Spin"),
-                    );
-                    ui.add(
+                    if let Some(index) = removed {
+                        settings.remove_tombola(index);
+                        midi_config.learning = midi_config
+                            .learning
+                            .and_then(|parameter| parameter.without_tombola(index));
+                        shape_editor.tombola = None;
+                    }
+
+                    if ui.button("Add Tombola").clicked() {
+                        settings.world.tombolas.push(TombolaSettings::default());
+                    }
+                });
+                ui.collapsing("World", |ui| {
+                    let response = ui.add(
                         egui::Slider::new(&mut settings.world.bounciness, 0.0..=1.0)
                             .text("Bounciness")
                             .fixed_decimals(2),
                     );
-                    ui.add(
+                    learnable(&response, &mut midi_config.learning, Parameter::Bounciness);
+                    let response = ui.add(
                         egui::Slider::new(&mut settings.world.gravity, 0.0..=1.5)
                             .text("Gravity")
                             .fixed_decimals(2),
                     );
+                    learnable(&response, &mut midi_config.learning, Parameter::Gravity);
+
+                    ui.checkbox(
+                        &mut settings.world.ball_collisions,
+                        "Ball-to-Ball Collisions",
+                    );
 
                     ui.checkbox(&mut settings.world.max_balls.enabled, "Max Balls");
                     if settings.world.max_balls.enabled {
-                        ui.add(egui::Slider::new(
+                        let response = ui.add(egui::Slider::new(
                             &mut settings.world.max_balls.limit,
                             1..=20,
                         ));
+                        learnable(&response, &mut midi_config.learning, Parameter::MaxBalls);
                     }
 
                     ui.checkbox(
@@ -216,49 +530,607 @@ pub fn show_settings_menu(
                         ));
                     }
                 });
+                ui.collapsing("Balls", |ui| {
+                    let mut removed = None;
+                    let num_sizes = settings.world.palette.len();
+                    for (index, ball_size) in settings.world.palette.iter_mut().enumerate() {
+                        ui.push_id(("ball", index), |ui| {
+                            ui.horizontal(|ui| {
+                                ui.color_edit_button_rgb(&mut ball_size.color);
+                                ui.text_edit_singleline(&mut ball_size.name);
+
+                                if num_sizes > 1 && ui.button("Remove").clicked() {
+                                    removed = Some(index);
+                                }
+                            });
+                            ui.add(
+                                egui::Slider::new(
+                                    &mut ball_size.radius,
+                                    size::MIN_RADIUS..=size::MAX_RADIUS,
+                                )
+                                .text("Radius"),
+                            );
+                            ui.add(
+                                egui::Slider::new(&mut ball_size.brightness, 0.0..=3.0)
+                                    .text("Glow"),
+                            );
+                            ui.add(
+                                egui::Slider::new(&mut ball_size.density, 0.1..=10.0)
+                                    .text("Density")
+                                    .logarithmic(true),
+                            );
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut ball_size.restitution.enabled, "Bounciness");
+                                if ball_size.restitution.enabled {
+                                    ui.add(
+                                        egui::Slider::new(
+                                            &mut ball_size.restitution.value,
+                                            0.0..=1.0,
+                                        )
+                                        .fixed_decimals(2),
+                                    );
+                                }
+                            });
+                            ui.add(egui::Slider::new(&mut ball_size.octave, -2..=8).text("Octave"));
+                            ui.add(
+                                egui::Slider::new(&mut ball_size.transpose, -24..=24)
+                                    .text("Transpose"),
+                            );
+                            ui.add(
+                                egui::Slider::new(
+                                    &mut ball_size.channel,
+                                    midi::MIN_CHANNEL..=midi::MAX_CHANNEL,
+                                )
+                                .text("Channel"),
+                            );
+                            ui.collapsing("Synth Patch", |ui| {
+                                show_patch(ui, &mut ball_size.patch);
+                            });
+                        });
+                        ui.add_space(10.0);
+                    }
+
+                    if let Some(index) = removed {
+                        settings.remove_ball_size(index);
+                        removed_ball_size = Some(index);
+                    }
+
+                    if ui.button("Add Ball").clicked() {
+                        settings.world.palette.push(BallSize::default());
+                    }
+                });
+                ui.collapsing("Emitters", |ui| {
+                    let ball_names: Vec<String> = settings
+                        .world
+                        .palette
+                        .iter()
+                        .map(|ball_size| ball_size.name.clone())
+                        .collect();
+                    let mut removed = None;
+                    for (index, emitter) in settings.world.emitters.iter_mut().enumerate() {
+                        ui.push_id(index, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut emitter.enabled, format!("Emitter {}", index + 1));
+
+                                egui::ComboBox::from_id_salt("emitter_size")
+                                    .selected_text(
+                                        ball_names.get(emitter.size).cloned().unwrap_or_default(),
+                                    )
+                                    .show_ui(ui, |ui| {
+                                        for (size, name) in ball_names.iter().enumerate() {
+                                            ui.selectable_value(&mut emitter.size, size, name);
+                                        }
+                                    });
+
+                                if ui.button("Remove").clicked() {
+                                    removed = Some(index);
+                                }
+                            });
+
+                            ui.horizontal(|ui| {
+                                ui.label("Position");
+                                ui.add(egui::DragValue::new(&mut emitter.position.0).prefix("x: "));
+                                ui.add(egui::DragValue::new(&mut emitter.position.1).prefix("y: "));
+                            });
+                            ui.add(
+                                egui::Slider::new(&mut emitter.angle, -180.0..=180.0).text("Angle"),
+                            );
+                            ui.add(
+                                egui::Slider::new(&mut emitter.speed, 0.0..=1000.0).text("Speed"),
+                            );
+
+                            ui.horizontal(|ui| {
+                                ui.label("Every");
+                                egui::ComboBox::from_id_salt("emitter_division")
+                                    .selected_text(emitter.division.to_string())
+                                    .show_ui(ui, |ui| {
+                                        for division in Division::iter() {
+                                            ui.selectable_value(
+                                                &mut emitter.division,
+                                                division,
+                                                division.to_string(),
+                                            );
+                                        }
+                                    });
+                            });
+
+                            ui.checkbox(&mut emitter.pattern.enabled, "Euclidean Pattern");
+                            if emitter.pattern.enabled {
+                                ui.add(
+                                    egui::Slider::new(&mut emitter.pattern.steps, 1..=32)
+                                        .text("Steps"),
+                                );
+                                let steps = emitter.pattern.steps;
+                                ui.add(
+                                    egui::Slider::new(&mut emitter.pattern.pulses, 0..=steps)
+                                        .text("Pulses"),
+                                );
+                                ui.add(
+                                    egui::Slider::new(
+                                        &mut emitter.pattern.rotation,
+                                        0..=steps.saturating_sub(1),
+                                    )
+                                    .text("Rotation"),
+                                );
+                            }
+                        });
+                        ui.add_space(10.0);
+                    }
+
+                    if let Some(index) = removed {
+                        settings.world.emitters.remove(index);
+                    }
+
+                    if ui.button("Add Emitter").clicked() {
+                        settings.world.emitters.push(EmitterSettings::default());
+                    }
+                });
                 ui.collapsing("MIDI", |ui| {
                     ui.label("Device");
-                    let ports = midi_config.get_ports();
-                    if !ports.is_empty() {
+                    let port_names = midi_config.get_port_names();
+                    if !port_names.is_empty() {
                         egui::ComboBox::from_id_salt("midi_ports")
                             .selected_text(midi_config.active_port.clone())
                             .show_ui(ui, |ui| {
-                                for port in midi_config.get_ports() {
+                                for name in port_names {
                                     ui.selectable_value(
                                         &mut midi_config.active_port,
+                                        name.clone(),
+                                        name,
+                                    );
+                                }
+                            });
+                    } else {
+                        ui.label("No midi ports detected");
+                    }
+                    ui.add_space(10.0);
+
+                    ui.label("Input Device");
+                    let input_ports = midi_config.get_input_ports();
+                    if !input_ports.is_empty() {
+                        egui::ComboBox::from_id_salt("midi_input_ports")
+                            .selected_text(if midi_config.active_input_port.is_empty() {
+                                "None".to_string()
+                            } else {
+                                midi_config.active_input_port.clone()
+                            })
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(
+                                    &mut midi_config.active_input_port,
+                                    String::new(),
+                                    "None",
+                                );
+                                for port in input_ports {
+                                    ui.selectable_value(
+                                        &mut midi_config.active_input_port,
                                         port.name.clone(),
                                         port.name,
                                     );
                                 }
                             });
                     } else {
-                        ui.label("No midi ports detected");
+                        ui.label("No midi input ports detected");
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label("Keyboard");
+                        egui::ComboBox::from_id_salt("keyboard_mode")
+                            .selected_text(settings.midi.keyboard.mode.to_string())
+                            .show_ui(ui, |ui| {
+                                for mode in KeyboardMode::iter() {
+                                    ui.selectable_value(
+                                        &mut settings.midi.keyboard.mode,
+                                        mode,
+                                        mode.to_string(),
+                                    );
+                                }
+                            });
+                    });
+                    if settings.midi.keyboard.mode != KeyboardMode::Off {
+                        let num_tombolas = settings.world.tombolas.len();
+                        let keyboard = &mut settings.midi.keyboard;
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut keyboard.channel.enabled, "Only Channel");
+                            if keyboard.channel.enabled {
+                                ui.add(
+                                    egui::DragValue::new(&mut keyboard.channel.value)
+                                        .range(midi::MIN_CHANNEL..=midi::MAX_CHANNEL),
+                                );
+                            }
+                        });
+                        match keyboard.mode {
+                            KeyboardMode::SpawnBalls => {
+                                ui.horizontal(|ui| {
+                                    ui.label("Key Range");
+                                    ui.add(
+                                        egui::DragValue::new(&mut keyboard.lowest)
+                                            .range(midi::MIN_PITCH..=midi::MAX_PITCH)
+                                            .custom_formatter(|value, _| {
+                                                midi::pitch_name(value as u8)
+                                            }),
+                                    );
+                                    ui.add(
+                                        egui::DragValue::new(&mut keyboard.highest)
+                                            .range(midi::MIN_PITCH..=midi::MAX_PITCH)
+                                            .custom_formatter(|value, _| {
+                                                midi::pitch_name(value as u8)
+                                            }),
+                                    );
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Drop Range");
+                                    ui.add(
+                                        egui::DragValue::new(&mut keyboard.drop_range.0)
+                                            .prefix("x: "),
+                                    );
+                                    ui.add(
+                                        egui::DragValue::new(&mut keyboard.drop_range.1)
+                                            .prefix("x: "),
+                                    );
+                                    ui.add(
+                                        egui::DragValue::new(&mut keyboard.drop_height)
+                                            .prefix("y: "),
+                                    );
+                                });
+                            }
+                            KeyboardMode::RetunePads => {
+                                ui.add(
+                                    egui::DragValue::new(&mut keyboard.tombola)
+                                        .range(0..=num_tombolas.saturating_sub(1))
+                                        .custom_formatter(|value, _| {
+                                            format!("Tombola {}", value as usize + 1)
+                                        }),
+                                );
+                            }
+                            KeyboardMode::Off => {}
+                        }
                     }
                     ui.add_space(10.0);
 
-                    ui.label("Notes");
-                    for (index, current_note) in
-                        &mut settings.midi.tombola_notes.iter_mut().enumerate()
-                    {
-                        egui::ComboBox::from_id_salt(index)
-                            .selected_text(current_note.to_string())
+                    ui.label("Key");
+                    let previous_root = settings.midi.key.root;
+                    let previous_mask = settings.midi.key.to_mask();
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_salt("key_root")
+                            .selected_text(settings.midi.key.root.to_string())
                             .show_ui(ui, |ui| {
                                 for note in midi::Note::iter() {
-                                    ui.selectable_value(current_note, note, note.to_string());
+                                    ui.selectable_value(
+                                        &mut settings.midi.key.root,
+                                        note,
+                                        note.to_string(),
+                                    );
+                                }
+                            });
+
+                        egui::ComboBox::from_id_salt("key_scale")
+                            .selected_text(settings.midi.key.scale.to_string())
+                            .show_ui(ui, |ui| {
+                                for scale in Scale::iter() {
+                                    ui.selectable_value(
+                                        &mut settings.midi.key.scale,
+                                        scale,
+                                        scale.to_string(),
+                                    );
+                                }
+                            });
+                    });
+
+                    let root = settings.midi.key.root;
+                    if settings.midi.key.scale == Scale::Custom {
+                        ui.horizontal_wrapped(|ui| {
+                            for (interval, enabled) in
+                                settings.midi.key.custom_intervals.iter_mut().enumerate()
+                            {
+                                ui.add_enabled(
+                                    interval != 0,
+                                    egui::Checkbox::new(
+                                        enabled,
+                                        root.transpose(interval as i32).to_string(),
+                                    ),
+                                );
+                            }
+                        });
+                    }
+
+                    let mask = settings.midi.key.to_mask();
+                    for tombola in settings.world.tombolas.iter_mut() {
+                        if root != previous_root {
+                            scale::change_key(
+                                tombola.notes_mut(),
+                                previous_root,
+                                root,
+                                &previous_mask,
+                            );
+                        }
+                        if mask != previous_mask {
+                            for note in tombola.notes_mut() {
+                                *note = scale::snap(root, &mask, *note);
+                            }
+                        }
+                    }
+                    ui.add_space(10.0);
+
+                    for (tombola_index, tombola) in settings.world.tombolas.iter_mut().enumerate() {
+                        ui.collapsing(format!("Tombola {} Pads", tombola_index + 1), |ui| {
+                            ui.horizontal(|ui| {
+                                if ui.button("Fill From Scale").clicked() {
+                                    scale::fill(tombola.notes_mut(), root, &mask);
+                                }
+                                if ui.button("Randomize").clicked() {
+                                    scale::randomize(tombola.notes_mut(), root, &mask);
+                                }
+                            });
+                            ui.add_space(10.0);
+
+                            ui.label("Notes");
+                            for (index, pad) in tombola.pads.iter_mut().enumerate() {
+                                let current_note = &mut pad.note;
+                                let fixed_pitch = &mut pad.fixed_pitch;
+                                let pad_channel = &mut pad.channel;
+                                ui.horizontal(|ui| {
+                                    if fixed_pitch.enabled {
+                                        ui.add(
+                                            egui::DragValue::new(&mut fixed_pitch.value)
+                                                .range(midi::MIN_PITCH..=midi::MAX_PITCH)
+                                                .custom_formatter(|value, _| {
+                                                    midi::pitch_name(value as u8)
+                                                }),
+                                        );
+                                    } else {
+                                        let response = egui::ComboBox::from_id_salt(index)
+                                            .selected_text(current_note.to_string())
+                                            .show_ui(ui, |ui| {
+                                                for note in midi::Note::iter().filter(|note| {
+                                                    scale::contains(root, &mask, *note)
+                                                }) {
+                                                    ui.selectable_value(
+                                                        current_note,
+                                                        note,
+                                                        note.to_string(),
+                                                    );
+                                                }
+                                            })
+                                            .response;
+                                        learnable(
+                                            &response,
+                                            &mut midi_config.learning,
+                                            Parameter::PadNote(tombola_index, index),
+                                        );
+                                    }
+
+                                    ui.checkbox(&mut fixed_pitch.enabled, "Fixed");
+                                    ui.checkbox(&mut pad_channel.enabled, "Channel");
+                                    if pad_channel.enabled {
+                                        ui.add(egui::Slider::new(
+                                            &mut pad_channel.value,
+                                            midi::MIN_CHANNEL..=midi::MAX_CHANNEL,
+                                        ));
+                                    }
+                                });
+                            }
+                            ui.add_space(10.0);
+
+                            ui.label("Chords");
+                            for (index, pad) in tombola.pads.iter_mut().enumerate() {
+                                let pad_chord = &mut pad.chord;
+                                ui.horizontal(|ui| {
+                                    ui.label(format!("Pad {}", index + 1));
+                                    egui::ComboBox::from_id_salt(("chord", index))
+                                        .selected_text(pad_chord.chord.to_string())
+                                        .show_ui(ui, |ui| {
+                                            for chord in Chord::iter() {
+                                                ui.selectable_value(
+                                                    &mut pad_chord.chord,
+                                                    chord,
+                                                    chord.to_string(),
+                                                );
+                                            }
+                                        });
+
+                                    if pad_chord.chord != Chord::Single {
+                                        egui::ComboBox::from_id_salt(("voicing", index))
+                                            .selected_text(pad_chord.voicing.to_string())
+                                            .show_ui(ui, |ui| {
+                                                for voicing in Voicing::iter() {
+                                                    ui.selectable_value(
+                                                        &mut pad_chord.voicing,
+                                                        voicing,
+                                                        voicing.to_string(),
+                                                    );
+                                                }
+                                            });
+
+                                        let max_inversion =
+                                            pad_chord.intervals().len().saturating_sub(1);
+                                        ui.add(
+                                            egui::Slider::new(
+                                                &mut pad_chord.inversion,
+                                                0..=max_inversion,
+                                            )
+                                            .text("Inversion"),
+                                        );
+                                    }
+                                });
+
+                                if pad_chord.chord == Chord::Custom {
+                                    ui.horizontal(|ui| {
+                                        for interval in pad_chord.custom_intervals.iter_mut() {
+                                            ui.add(egui::DragValue::new(interval).range(-24..=36));
+                                        }
+
+                                        if ui.button("+").clicked() {
+                                            let next =
+                                                pad_chord.custom_intervals.last().unwrap_or(&0) + 3;
+                                            pad_chord.custom_intervals.push(next);
+                                        }
+                                        if ui.button("-").clicked()
+                                            && pad_chord.custom_intervals.len() > 1
+                                        {
+                                            pad_chord.custom_intervals.pop();
+                                        }
+                                    });
+                                }
+                            }
+                            ui.add_space(10.0);
+
+                            ui.label("Samples");
+                            for (index, pad) in tombola.pads.iter_mut().enumerate() {
+                                let pad_sample = &mut pad.sample;
+                                ui.horizontal(|ui| {
+                                    ui.label(format!("Pad {}", index + 1));
+                                    ui.checkbox(&mut pad_sample.enabled, "Sample");
+                                    if pad_sample.enabled {
+                                        ui.add(
+                                            egui::TextEdit::singleline(&mut pad_sample.path)
+                                                .hint_text("path/to/sample.wav"),
+                                        );
+                                    }
+                                });
+
+                                if pad_sample.enabled {
+                                    ui.horizontal(|ui| {
+                                        egui::ComboBox::from_id_salt(("sample_mode", index))
+                                            .selected_text(pad_sample.mode.to_string())
+                                            .show_ui(ui, |ui| {
+                                                for mode in SampleMode::iter() {
+                                                    ui.selectable_value(
+                                                        &mut pad_sample.mode,
+                                                        mode,
+                                                        mode.to_string(),
+                                                    );
+                                                }
+                                            });
+                                        ui.add(
+                                            egui::Slider::new(&mut pad_sample.gain, 0.0..=2.0)
+                                                .text("Gain"),
+                                        );
+                                    });
+                                    ui.horizontal(|ui| {
+                                        ui.checkbox(&mut pad_sample.pitch_by_size, "Pitch by Size");
+                                        ui.add(
+                                            egui::Slider::new(
+                                                &mut pad_sample.choke,
+                                                0..=sampler::MAX_CHOKE_GROUP,
+                                            )
+                                            .text("Choke Group")
+                                            .custom_formatter(|value, _| {
+                                                if value == 0.0 {
+                                                    "Off".to_string()
+                                                } else {
+                                                    format!("{}", value)
+                                                }
+                                            }),
+                                        );
+                                    });
+                                }
+                            }
+                        });
+                    }
+                    ui.add_space(10.0);
+
+                    ui.label("Pitch");
+                    ui.add(
+                        egui::Slider::new(&mut settings.midi.pitch.transpose, -24..=24)
+                            .text("Transpose"),
+                    );
+                    ui.horizontal(|ui| {
+                        ui.label("Out of Range");
+                        egui::ComboBox::from_id_salt("out_of_range")
+                            .selected_text(settings.midi.pitch.out_of_range.to_string())
+                            .show_ui(ui, |ui| {
+                                for out_of_range in midi::OutOfRange::iter() {
+                                    ui.selectable_value(
+                                        &mut settings.midi.pitch.out_of_range,
+                                        out_of_range,
+                                        out_of_range.to_string(),
+                                    );
+                                }
+                            });
+                    });
+                    ui.add_space(10.0);
+
+                    ui.checkbox(
+                        &mut settings.midi.collision_notes.enabled,
+                        "Ball Collision Notes",
+                    );
+                    if settings.midi.collision_notes.enabled {
+                        let collision_notes = &mut settings.midi.collision_notes;
+                        egui::ComboBox::from_id_salt("collision_note")
+                            .selected_text(collision_notes.note.to_string())
+                            .show_ui(ui, |ui| {
+                                for note in CollisionNote::iter() {
+                                    ui.selectable_value(
+                                        &mut collision_notes.note,
+                                        note,
+                                        note.to_string(),
+                                    );
                                 }
                             });
+                        if collision_notes.note == CollisionNote::Fixed {
+                            ui.add(
+                                egui::DragValue::new(&mut collision_notes.pitch)
+                                    .range(midi::MIN_PITCH..=midi::MAX_PITCH)
+                                    .custom_formatter(|value, _| midi::pitch_name(value as u8)),
+                            );
+                        }
+                        ui.add(
+                            egui::Slider::new(
+                                &mut collision_notes.channel,
+                                midi::MIN_CHANNEL..=midi::MAX_CHANNEL,
+                            )
+                            .text("Channel"),
+                        );
+                        ui.collapsing("Collision Velocity", |ui| {
+                            show_velocity_curve(
+                                ui,
+                                "collision_velocity",
+                                &mut collision_notes.velocity,
+                            );
+                        });
+                        if !settings.world.ball_collisions {
+                            ui.label("Enable ball-to-ball collisions in the World section");
+                        }
                     }
                     ui.add_space(10.0);
 
+                    ui.collapsing("Velocity Curve", |ui| {
+                        show_velocity_curve(ui, "pad_velocity", &mut settings.midi.velocity);
+                    });
+
                     ui.checkbox(
                         &mut settings.midi.fixed_note_velocity.enabled,
                         "Fixed Note Velocity",
                     );
                     if settings.midi.fixed_note_velocity.enabled {
-                        ui.add(egui::Slider::new(
+                        let response = ui.add(egui::Slider::new(
                             &mut settings.midi.fixed_note_velocity.value,
-                            0..=127,
+                            midi::MIN_VELOCITY..=midi::MAX_VELOCITY,
                         ));
+                        learnable(
+                            &response,
+                            &mut midi_config.learning,
+                            Parameter::FixedVelocity,
+                        );
                     }
 
                     ui.checkbox(
@@ -266,19 +1138,480 @@ pub fn show_settings_menu(
                         "Fixed Note Length",
                     );
                     if settings.midi.fixed_note_length.enabled {
-                        ui.add(
+                        let response = ui.add(
                             egui::Slider::new(
                                 &mut settings.midi.fixed_note_length.value,
                                 10..=1000,
                             )
                             .suffix("ms"),
                         );
+                        learnable(&response, &mut midi_config.learning, Parameter::FixedLength);
+                    }
+                    ui.add_space(10.0);
+
+                    ui.checkbox(&mut settings.midi.mpe.enabled, "MPE");
+                    if settings.midi.mpe.enabled {
+                        let mpe = &mut settings.midi.mpe;
+                        egui::ComboBox::from_id_salt("mpe_zone")
+                            .selected_text(mpe.zone.to_string())
+                            .show_ui(ui, |ui| {
+                                for zone in Zone::iter() {
+                                    ui.selectable_value(&mut mpe.zone, zone, zone.to_string());
+                                }
+                            });
+                        ui.add(
+                            egui::Slider::new(
+                                &mut mpe.member_channels,
+                                1..=mpe::MAX_MEMBER_CHANNELS,
+                            )
+                            .text("Member Channels"),
+                        );
+                        ui.add(
+                            egui::Slider::new(
+                                &mut mpe.pitch_bend_range,
+                                1..=mpe::MAX_PITCH_BEND_RANGE,
+                            )
+                            .suffix(" semitones")
+                            .text("Pitch Bend Range"),
+                        );
+                        ui.add(
+                            egui::Slider::new(&mut mpe.bend_speed, 10.0..=2000.0)
+                                .text("Horizontal Speed for Full Bend"),
+                        );
+                        ui.add(
+                            egui::Slider::new(&mut mpe.pressure_speed, 10.0..=2000.0)
+                                .text("Speed for Full Pressure"),
+                        );
+                        ui.horizontal(|ui| {
+                            ui.label("Timbre (CC74) Height Range");
+                            ui.add(egui::DragValue::new(&mut mpe.timbre_range.0));
+                            ui.add(egui::DragValue::new(&mut mpe.timbre_range.1));
+                        });
+                    }
+                });
+                ui.collapsing("Modulation", |ui| {
+                    let modulation = &mut settings.midi.modulation;
+                    ui.add(
+                        egui::Slider::new(
+                            &mut modulation.rate,
+                            modulation::MIN_RATE..=modulation::MAX_RATE,
+                        )
+                        .logarithmic(true)
+                        .suffix("Hz")
+                        .text("Send Rate"),
+                    );
+                    ui.add_space(10.0);
+
+                    let num_tombolas = settings.world.tombolas.len();
+                    let mut removed = None;
+                    for (index, mapping) in modulation.mappings.iter_mut().enumerate() {
+                        ui.push_id(index, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut mapping.enabled, format!("Mapping {}", index + 1));
+                                if ui.button("Remove").clicked() {
+                                    removed = Some(index);
+                                }
+                            });
+
+                            ui.horizontal(|ui| {
+                                ui.label("Source");
+                                let previous = mapping.source;
+                                egui::ComboBox::from_id_salt("modulation_source")
+                                    .selected_text(mapping.source.to_string())
+                                    .show_ui(ui, |ui| {
+                                        for source in Source::iter() {
+                                            ui.selectable_value(
+                                                &mut mapping.source,
+                                                source,
+                                                source.to_string(),
+                                            );
+                                        }
+                                    });
+                                if mapping.source != previous {
+                                    mapping.range = mapping.source.default_range();
+                                }
+                                if mapping.source == Source::TombolaAngle {
+                                    ui.add(
+                                        egui::DragValue::new(&mut mapping.tombola)
+                                            .range(0..=num_tombolas.saturating_sub(1))
+                                            .custom_formatter(|value, _| {
+                                                format!("Tombola {}", value as usize + 1)
+                                            }),
+                                    );
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Input Range");
+                                ui.add(egui::DragValue::new(&mut mapping.range.0));
+                                ui.add(egui::DragValue::new(&mut mapping.range.1));
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Curve");
+                                egui::ComboBox::from_id_salt("modulation_curve")
+                                    .selected_text(mapping.curve.to_string())
+                                    .show_ui(ui, |ui| {
+                                        for curve in Curve::iter() {
+                                            ui.selectable_value(
+                                                &mut mapping.curve,
+                                                curve,
+                                                curve.to_string(),
+                                            );
+                                        }
+                                    });
+                            });
+                            ui.add(
+                                egui::Slider::new(&mut mapping.output.0, 0.0..=1.0)
+                                    .text("Output Min"),
+                            );
+                            ui.add(
+                                egui::Slider::new(&mut mapping.output.1, 0.0..=1.0)
+                                    .text("Output Max"),
+                            );
+
+                            ui.horizontal(|ui| {
+                                egui::ComboBox::from_id_salt("modulation_destination")
+                                    .selected_text(mapping.destination.to_string())
+                                    .show_ui(ui, |ui| {
+                                        for destination in Destination::iter() {
+                                            ui.selectable_value(
+                                                &mut mapping.destination,
+                                                destination,
+                                                destination.to_string(),
+                                            );
+                                        }
+                                    });
+                                if mapping.destination == Destination::ControlChange {
+                                    ui.add(
+                                        egui::DragValue::new(&mut mapping.controller)
+                                            .range(0..=midi::MAX_CONTROLLER)
+                                            .prefix("CC "),
+                                    );
+                                }
+                            });
+                            ui.add(
+                                egui::Slider::new(
+                                    &mut mapping.channel,
+                                    midi::MIN_CHANNEL..=midi::MAX_CHANNEL,
+                                )
+                                .text("Channel"),
+                            );
+                        });
+                        ui.add_space(10.0);
+                    }
+
+                    if let Some(index) = removed {
+                        modulation.mappings.remove(index);
+                    }
+
+                    if ui.button("Add Mapping").clicked() {
+                        modulation.mappings.push(ModulationSettings::default());
+                    }
+                });
+                ui.collapsing("MIDI Learn", |ui| {
+                    if let Some(parameter) = midi_config.learning {
+                        ui.horizontal(|ui| {
+                            ui.label(format!("Move a control to bind it to {}", parameter));
+                            if ui.button("Cancel").clicked() {
+                                midi_config.learning = None;
+                            }
+                        });
+                    } else {
+                        ui.label(
+                            "Right-click a control and choose 'MIDI Learn' to bind a CC to it",
+                        );
+                    }
+                    ui.add_space(10.0);
+
+                    let mut removed = None;
+                    for (index, binding) in settings.midi.bindings.iter_mut().enumerate() {
+                        ui.push_id(("binding", index), |ui| {
+                            ui.horizontal(|ui| {
+                                ui.label(binding.parameter.to_string());
+                                if ui.button("Remove").clicked() {
+                                    removed = Some(index);
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                ui.add(
+                                    egui::DragValue::new(&mut binding.channel)
+                                        .range(midi::MIN_CHANNEL..=midi::MAX_CHANNEL)
+                                        .prefix("Channel "),
+                                );
+                                ui.add(
+                                    egui::DragValue::new(&mut binding.controller)
+                                        .range(0..=midi::MAX_CONTROLLER)
+                                        .prefix("CC "),
+                                );
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Range");
+                                ui.add(egui::DragValue::new(&mut binding.range.0).speed(0.01));
+                                ui.add(egui::DragValue::new(&mut binding.range.1).speed(0.01));
+                            });
+                        });
+                        ui.add_space(10.0);
+                    }
+
+                    if let Some(index) = removed {
+                        settings.midi.bindings.remove(index);
+                    }
+                });
+                ui.collapsing("Transport", |ui| {
+                    ui.horizontal(|ui| {
+                        if transport.is_playing() {
+                            if ui.button("Stop").clicked() {
+                                transport.stop();
+                            }
+                        } else {
+                            if ui.button("Play").clicked() {
+                                transport.start();
+                            }
+                            if ui.button("Continue").clicked() {
+                                transport.resume();
+                            }
+                        }
+                    });
+                    ui.add_space(10.0);
+
+                    ui.label("Clock Source");
+                    egui::ComboBox::from_id_salt("clock_source")
+                        .selected_text(settings.midi.clock.source.to_string())
+                        .show_ui(ui, |ui| {
+                            for source in ClockSource::iter() {
+                                ui.selectable_value(
+                                    &mut settings.midi.clock.source,
+                                    source,
+                                    source.to_string(),
+                                );
+                            }
+                        });
+
+                    match settings.midi.clock.source {
+                        ClockSource::Internal => {
+                            ui.checkbox(&mut settings.midi.clock.enabled, "Send MIDI Clock");
+                            ui.add(
+                                egui::Slider::new(&mut settings.midi.clock.bpm, 20.0..=300.0)
+                                    .text("BPM")
+                                    .fixed_decimals(1),
+                            );
+                        }
+                        ClockSource::External => {
+                            if let Some(bpm) = transport.external_bpm() {
+                                ui.label(format!("Tempo: {:.1} BPM", bpm));
+                            } else {
+                                ui.label("Waiting for MIDI clock on the input device");
+                            }
+                        }
+                    }
+                    ui.add_space(10.0);
+
+                    ui.label("Quantize");
+                    egui::ComboBox::from_id_salt("quantize")
+                        .selected_text(settings.midi.quantize.to_string())
+                        .show_ui(ui, |ui| {
+                            for quantize in Quantize::iter() {
+                                ui.selectable_value(
+                                    &mut settings.midi.quantize,
+                                    quantize,
+                                    quantize.to_string(),
+                                );
+                            }
+                        });
+                });
+                ui.collapsing("Synth", |ui| {
+                    let synth = &mut settings.synth;
+                    ui.horizontal(|ui| {
+                        ui.label("Output");
+                        egui::ComboBox::from_id_salt("synth_output")
+                            .selected_text(synth.output.to_string())
+                            .show_ui(ui, |ui| {
+                                for output in SynthOutput::iter() {
+                                    ui.selectable_value(
+                                        &mut synth.output,
+                                        output,
+                                        output.to_string(),
+                                    );
+                                }
+                            });
+                    });
+                    ui.add(egui::Slider::new(&mut synth.volume, 0.0..=1.0).text("Volume"));
+                    ui.add(
+                        egui::Slider::new(&mut synth.polyphony, 1..=synth::MAX_POLYPHONY)
+                            .text("Voices"),
+                    );
+                    ui.label("Each ball's sound is set in its 'Synth Patch' in the Balls section");
+                });
+                ui.collapsing("OSC", |ui| {
+                    let osc = &mut settings.osc;
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut osc.enabled, "Listen on");
+                        ui.add(egui::TextEdit::singleline(&mut osc.address).desired_width(100.0));
+                        ui.label("Port");
+                        ui.add(egui::DragValue::new(&mut osc.port).range(1024..=65535));
+                    });
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut osc.output, "Send Pad Hits To");
+                        ui.text_edit_singleline(&mut osc.target);
+                    });
+                });
+                ui.collapsing("Recording", |ui| {
+                    ui.horizontal(|ui| {
+                        if recorder.is_recording() {
+                            if ui.button("Stop").clicked() {
+                                recorder.stop();
+                            }
+                            ui.label(format!("Recording... ({} notes)", recorder.num_notes()));
+                        } else if ui.button("Record").clicked() {
+                            recorder.start();
+                        }
+                    });
+
+                    if recorder.has_recording() {
+                        ui.add_space(10.0);
+                        ui.label(format!("Recorded {} notes", recorder.num_notes()));
+                        ui.horizontal(|ui| {
+                            ui.text_edit_singleline(&mut recorder.file_name);
+
+                            if ui.button("Export").clicked()
+                                && !recorder.file_name.trim().is_empty()
+                            {
+                                let path = recorder::path_for(recorder.file_name.trim());
+                                match recorder.export(&path, settings.midi.clock.bpm) {
+                                    Ok(()) => println!("Exported recording to {}", path.display()),
+                                    Err(error) => println!(
+                                        "Failed to export recording {}: {}",
+                                        path.display(),
+                                        error
+                                    ),
+                                }
+                            }
+                        });
                     }
                 });
             });
 
-        return egui.ctx_mut().is_pointer_over_area();
+        return SettingsMenu {
+            hovered: egui.ctx_mut().is_pointer_over_area(),
+            removed_ball_size,
+        };
     }
 
-    false
+    SettingsMenu::default()
+}
+
+fn show_velocity_curve(ui: &mut egui::Ui, id: &str, velocity: &mut VelocityCurve) {
+    const SAMPLES: usize = 64;
+
+    ui.push_id(id, |ui| {
+        egui::ComboBox::from_id_salt("curve")
+            .selected_text(velocity.curve.to_string())
+            .show_ui(ui, |ui| {
+                for curve in Curve::iter() {
+                    ui.selectable_value(&mut velocity.curve, curve, curve.to_string());
+                }
+            });
+        ui.add(
+            egui::Slider::new(&mut velocity.min_speed, 0.0..=velocity::MAX_SPEED).text("Min Speed"),
+        );
+        ui.add(
+            egui::Slider::new(&mut velocity.max_speed, 0.0..=velocity::MAX_SPEED).text("Max Speed"),
+        );
+        velocity.max_speed = velocity.max_speed.max(velocity.min_speed);
+        ui.add(
+            egui::Slider::new(
+                &mut velocity.min_velocity,
+                midi::MIN_VELOCITY..=midi::MAX_VELOCITY,
+            )
+            .text("Min Velocity"),
+        );
+        ui.add(
+            egui::Slider::new(
+                &mut velocity.max_velocity,
+                midi::MIN_VELOCITY..=midi::MAX_VELOCITY,
+            )
+            .text("Max Velocity"),
+        );
+        velocity.max_velocity = velocity.max_velocity.max(velocity.min_velocity);
+
+        let (response, painter) = ui.allocate_painter(
+            egui::vec2(ui.available_width(), 100.0),
+            egui::Sense::hover(),
+        );
+        let rect = response.rect;
+        painter.rect_stroke(
+            rect,
+            0.0,
+            ui.visuals().widgets.noninteractive.bg_stroke,
+            egui::StrokeKind::Inside,
+        );
+
+        let points = (0..=SAMPLES)
+            .map(|i| {
+                let amount = i as f32 / SAMPLES as f32;
+                let value = velocity.to_velocity(amount * velocity::MAX_SPEED) as f32
+                    / midi::MAX_VELOCITY as f32;
+                egui::pos2(
+                    rect.left() + amount * rect.width(),
+                    rect.bottom() - value * rect.height(),
+                )
+            })
+            .collect();
+        painter.add(egui::Shape::line(
+            points,
+            egui::Stroke::new(2.0, ui.visuals().selection.bg_fill),
+        ));
+    });
+}
+
+fn learnable(response: &egui::Response, learning: &mut Option<Parameter>, parameter: Parameter) {
+    response.context_menu(|ui| {
+        if *learning == Some(parameter) {
+            if ui.button("Cancel MIDI Learn").clicked() {
+                *learning = None;
+                ui.close_menu();
+            }
+        } else if ui.button("MIDI Learn").clicked() {
+            *learning = Some(parameter);
+            ui.close_menu();
+        }
+    });
+}
+
+fn show_patch(ui: &mut egui::Ui, patch: &mut Patch) {
+    egui::ComboBox::from_id_salt("waveform")
+        .selected_text(patch.waveform.to_string())
+        .show_ui(ui, |ui| {
+            for waveform in Waveform::iter() {
+                ui.selectable_value(&mut patch.waveform, waveform, waveform.to_string());
+            }
+        });
+    ui.add(egui::Slider::new(&mut patch.fm_ratio, 0.5..=8.0).text("FM Ratio"));
+    ui.add(egui::Slider::new(&mut patch.fm_depth, 0.0..=2.0).text("FM Depth"));
+    ui.add(
+        egui::Slider::new(&mut patch.cutoff, 20.0..=12000.0)
+            .logarithmic(true)
+            .suffix("Hz")
+            .text("Cutoff"),
+    );
+    ui.add(egui::Slider::new(&mut patch.resonance, 0.0..=0.95).text("Resonance"));
+    ui.add(
+        egui::Slider::new(&mut patch.attack, 0.001..=2.0)
+            .logarithmic(true)
+            .suffix("s")
+            .text("Attack"),
+    );
+    ui.add(
+        egui::Slider::new(&mut patch.decay, 0.001..=2.0)
+            .logarithmic(true)
+            .suffix("s")
+            .text("Decay"),
+    );
+    ui.add(egui::Slider::new(&mut patch.sustain, 0.0..=1.0).text("Sustain"));
+    ui.add(
+        egui::Slider::new(&mut patch.release, 0.001..=4.0)
+            .logarithmic(true)
+            .suffix("s")
+            .text("Release"),
+    );
+    ui.add(egui::Slider::new(&mut patch.gain, 0.0..=1.0).text("Gain"));
 }
@@ -0,0 +1,89 @@
+use crate::midi;
+use bevy::math::ops::ln;
+use bevy::math::Vec2;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use strum_macros::EnumIter;
+
+pub const MAX_SPEED: f32 = 2000.0;
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Clone, Copy, PartialEq, EnumIter, Serialize, Deserialize)]
+pub enum Curve {
+    Linear,
+    Exponential,
+    Logarithmic,
+    SCurve,
+}
+
+impl Curve {
+    pub fn apply(&self, amount: f32) -> f32 {
+        let amount = amount.clamp(0.0, 1.0);
+
+        match self {
+            Curve::Linear => amount,
+            Curve::Exponential => amount * amount * amount,
+            Curve::Logarithmic => ln(1.0 + 9.0 * amount) / ln(10.0),
+            Curve::SCurve => amount * amount * (3.0 - 2.0 * amount),
+        }
+    }
+}
+
+impl fmt::Display for Curve {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Curve::Linear => write!(f, "Linear"),
+            Curve::Exponential => write!(f, "Exponential"),
+            Curve::Logarithmic => write!(f, "Logarithmic"),
+            Curve::SCurve => write!(f, "S-Curve"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VelocityCurve {
+    pub curve: Curve,
+    pub min_speed: f32,
+    pub max_speed: f32,
+    pub min_velocity: u8,
+    pub max_velocity: u8,
+}
+
+impl Default for VelocityCurve {
+    fn default() -> Self {
+        VelocityCurve {
+            curve: Curve::Linear,
+            min_speed: 50.0,
+            max_speed: 750.0,
+            min_velocity: midi::MIN_VELOCITY,
+            max_velocity: midi::MAX_VELOCITY,
+        }
+    }
+}
+
+impl VelocityCurve {
+    pub fn to_velocity(self, speed: f32) -> u8 {
+        let range = (self.max_speed - self.min_speed).max(f32::EPSILON);
+        let amount = self.curve.apply((speed - self.min_speed) / range);
+
+        let min = self
+            .min_velocity
+            .clamp(midi::MIN_VELOCITY, midi::MAX_VELOCITY);
+        let max = self.max_velocity.clamp(min, midi::MAX_VELOCITY);
+        let (min, max) = (min as f32, max as f32);
+
+        (min + (max - min) * amount).round() as u8
+    }
+}
+
+pub fn impact_speed(
+    ball_velocity: Vec2,
+    contact: Vec2,
+    centre: Vec2,
+    angular_velocity: f32,
+    normal: Vec2,
+) -> f32 {
+    let surface_velocity = (contact - centre).perp() * angular_velocity;
+    (ball_velocity - surface_velocity).dot(normal).abs()
+}
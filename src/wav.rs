@@ -0,0 +1,74 @@
+use std::fs;
+use std::path::Path;
+
+const CHANNELS: u16 = 1;
+const BITS_PER_SAMPLE: u16 = 16;
+const PCM_FORMAT: u16 = 1;
+const FORMAT_CHUNK_SIZE: u32 = 16;
+
+pub fn encode(samples: &[f32], sample_rate: u32) -> Vec<u8> {
+    let block_align = CHANNELS * BITS_PER_SAMPLE / 8;
+    let byte_rate = sample_rate * block_align as u32;
+    let data_size = (samples.len() * block_align as usize) as u32;
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_size).to_le_bytes());
+    bytes.extend_from_slice(b"WAVE");
+
+    bytes.extend_from_slice(b"fmt ");
+    bytes.extend_from_slice(&FORMAT_CHUNK_SIZE.to_le_bytes());
+    bytes.extend_from_slice(&PCM_FORMAT.to_le_bytes());
+    bytes.extend_from_slice(&CHANNELS.to_le_bytes());
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&byte_rate.to_le_bytes());
+    bytes.extend_from_slice(&block_align.to_le_bytes());
+    bytes.extend_from_slice(&BITS_PER_SAMPLE.to_le_bytes());
+
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_size.to_le_bytes());
+    for sample in samples {
+        let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    bytes
+}
+
+pub fn write(path: &Path, samples: &[f32], sample_rate: u32) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::write(path, encode(samples, sample_rate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_file() {
+        #[rustfmt::skip]
+        let expected = [
+            b'R', b'I', b'F', b'F', 0x2A, 0x00, 0x00, 0x00,
+            b'W', b'A', b'V', b'E',
+            b'f', b'm', b't', b' ', 0x10, 0x00, 0x00, 0x00,
+            0x01, 0x00, 0x01, 0x00,
+            0x44, 0xAC, 0x00, 0x00, 0x88, 0x58, 0x01, 0x00,
+            0x02, 0x00, 0x10, 0x00,
+            b'd', b'a', b't', b'a', 0x06, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0xFF, 0x7F, 0x01, 0x80,
+        ];
+
+        assert_eq!(encode(&[0.0, 1.0, -1.0], 44100), expected);
+    }
+
+    #[test]
+    fn clamps_out_of_range_samples() {
+        let bytes = encode(&[2.0, -2.0], 44100);
+
+        assert_eq!(bytes.len(), 44 + 4);
+        assert_eq!(bytes[44..], [0xFF, 0x7F, 0x01, 0x80]);
+    }
+}